@@ -1,15 +1,13 @@
 use std::{str::FromStr, sync::Arc};
-use tokio::time::{sleep, Duration};
-use tokio::sync::Mutex;
 
 use async_trait::async_trait;
-use logging::{DebugLogger, PrintLogger};
+use logging::DebugLogger;
 #[allow(unused_imports)]
-use message::{Message, MessageString, PayloadMessage};
-use network::{Network, Node};
+use message::{Envelope, Message, MessageString, PayloadMessage, Router};
+use network::latency::Constant;
+use network::{NetworkHandle, Node, ProcessingCosts, RunLimits, Simulation, Time};
 use node::{PassiveNode, SequentialNode};
 use utils::{skip, ProcessEffect};
-use env_logger;
 
 /// A ping message
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -23,9 +21,11 @@ impl<T: std::fmt::Debug + Clone + Eq + PartialEq + ToString> Ping<T> {
     pub fn payload(&self) -> &T {
         self.0.payload()
     }
+}
 
-    pub fn to_string(&self) -> String {
-        format!("Ping({:?})", self.payload())
+impl<T: std::fmt::Debug + Clone + Eq + PartialEq> std::fmt::Display for Ping<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Ping({:?})", self.0.payload())
     }
 }
 
@@ -57,9 +57,11 @@ impl<T: std::fmt::Debug + Clone + Eq + PartialEq> Pong<T> {
     pub fn payload(&self) -> &T {
         self.0.payload()
     }
+}
 
-    pub fn to_string(&self) -> String {
-        format!("Pong({:?})", self.payload())
+impl<T: std::fmt::Debug + Clone + Eq + PartialEq> std::fmt::Display for Pong<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Pong({:?})", self.0.payload())
     }
 }
 
@@ -88,6 +90,12 @@ impl PingNode {
     }
 }
 
+impl Default for PingNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl std::fmt::Debug for PingNode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "PingNode({})", self.ident())
@@ -96,7 +104,7 @@ impl std::fmt::Debug for PingNode {
 
 #[async_trait]
 impl Node for PingNode {
-    fn initialize(&mut self, ident: i32, network: Arc<Mutex<Network>>) {
+    fn initialize(&mut self, ident: i32, network: NetworkHandle) {
         self.base.initialize(ident, network);
     }
 
@@ -104,40 +112,57 @@ impl Node for PingNode {
         self.base.ident()
     }
 
-    fn network(&self) -> Arc<Mutex<Network>> {
+    fn network(&self) -> &NetworkHandle {
         self.base.network()
     }
 
     async fn run(&self) -> ProcessEffect {
         self.log("RUN", "ping node").await;
-        for i in 0..self.network().lock().await.num_nodes() {
-            let ping_i = Box::new(Ping::new(i));
-            self.send(i as i32, ping_i.box_clone(), None).await;
-            sleep(Duration::from_secs(1)).await;
-            self.send(i as i32, ping_i, None).await;
-            sleep(Duration::from_secs(2)).await;
+        self.on_timer(0).await
+    }
+
+    /// Pings every node twice, 1 time unit apart, then waits 2 time units
+    /// before moving on to the next node. The tag counts the pings sent so far.
+    async fn on_timer(&self, tag: u64) -> ProcessEffect {
+        let num_nodes = self.network().num_nodes() as u64;
+        let (i, repeat) = (tag / 2, tag % 2);
+        if i < num_nodes {
+            self.send(i as i32, Box::new(Ping::new(i as i32)), None).await;
+            let wait = if repeat == 0 { 1 } else { 2 };
+            self.set_timer(wait, tag + 1).await;
         }
         skip().await
     }
 
-    async fn handle(&self, sender: i32, message: Box<dyn Message>) -> ProcessEffect {
-        self.base.handle(sender, message).await
+    async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+        self.base.handle(envelope).await
     }
 }
 
-/// A node that responds to pings sequentially
+/// A node that responds to pings sequentially, spending 5 time units on each
 pub struct PongNode {
     base: SequentialNode,
+    costs: ProcessingCosts,
+    router: Router<Box<dyn Message>>,
 }
 
 impl PongNode {
     pub fn new() -> Self {
         PongNode {
             base: SequentialNode::new(),
+            costs: ProcessingCosts::new(0).with::<Ping<i32>>(5),
+            router: Router::<Box<dyn Message>>::new()
+                .on::<Ping<i32>>(|_, ping| Box::new(Pong::new(*ping.payload()))),
         }
     }
 }
 
+impl Default for PongNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl std::fmt::Debug for PongNode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "PongNode({})", self.ident())
@@ -146,7 +171,7 @@ impl std::fmt::Debug for PongNode {
 
 #[async_trait]
 impl Node for PongNode {
-    fn initialize(&mut self, ident: i32, network: Arc<Mutex<Network>>) {
+    fn initialize(&mut self, ident: i32, network: NetworkHandle) {
         self.base.initialize(ident, network);
     }
 
@@ -154,19 +179,28 @@ impl Node for PongNode {
         self.base.ident()
     }
 
-    fn network(&self) -> Arc<Mutex<Network>> {
+    fn network(&self) -> &NetworkHandle {
         self.base.network()
     }
 
-    async fn handle(&self, sender: i32, message: Box<dyn Message>) -> ProcessEffect {
-        if let Some(ping) = message.as_any_ref().downcast_ref::<Ping<i32>>() {
-            sleep(Duration::from_secs(5)).await;
-            let pong_i = Box::new(Pong::new(*ping.payload()));
-            self.send(sender, pong_i, None).await;
-        } else {
-            self.base.handle(sender, message).await;
+    fn is_sequential(&self) -> bool {
+        self.base.is_sequential()
+    }
+
+    fn processing_time(&self, message: &dyn Message) -> Time {
+        self.costs.cost(message)
+    }
+
+    async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+        let sender = envelope.sender;
+        match self.router.route(sender, envelope.message) {
+            Ok(pong) => self.send(sender, pong, None).await,
+            Err(unhandled) => {
+                self.log("unhandled", &unhandled.to_string()).await;
+                let message = unhandled.0;
+                self.base.handle(Envelope { message, ..envelope }).await
+            }
         }
-        skip().await
     }
 
     async fn run(&self) -> ProcessEffect {
@@ -175,36 +209,47 @@ impl Node for PongNode {
     }
 }
 
+/// Seed of the demo simulation
+const SEED: u64 = 0;
+
 /// Runs the demo
 #[tokio::main]
 pub async fn main() {
     env_logger::init();
-    let network: Arc<tokio::sync::Mutex<Network>> = Network::new(None, 4, Box::new(DebugLogger::default()));
+    let simulation = Simulation::new(SEED, Box::new(Constant(4)), Box::new(DebugLogger));
     // Add 10 PongNodes
     for _ in 0..10 {
-        network.lock().await.add_node(Arc::new(PongNode::new()));
+        simulation.add_node(Arc::new(PongNode::new())).await;
     }
 
     // Add PingNode
-    network.lock().await.add_node(Arc::new(PingNode::new()));
+    simulation.add_node(Arc::new(PingNode::new())).await;
     // Start all nodes
-    network.lock().await.start_all_nodes().await;
+    simulation.start_all_nodes().await;
 
-    // Run for 10 seconds
-    // TODO: Make shutdown more graceful
-    tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
-
-    // Stop all nodes on command
-    println!("Press Enter to stop network");
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input).unwrap();
+    // Process events in virtual time until the network goes quiet
+    let summary = simulation.run_to_completion(RunLimits::new()).await;
+    println!("{:?}", summary);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use logging::PrintLogger;
+    use bc::BlockRelayNode;
+    use logging::{Logger, PrintLogger, RecordingLogger};
+    use network::latency::Uniform;
+    use network::{
+        Adversary, Bandwidth, CallbackStrategy, Candidate, Delay, Equivocate, Explorer,
+        FaultConfig, Flooding, LinkFaults, MaxDelay, MessageSizes, Network, PartialSynchrony,
+        PartitionSchedule, Restart, RpcClient, RpcError, RpcReply, RpcResponse, RunSummary,
+        Silence, StopReason, Topology, Trace, TraceKind,
+    };
+    use node::{ByzantineNode, GossipConfig, GossipNode, GossipStats};
     use std::any::Any;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::BTreeSet;
+    use std::hash::{Hash, Hasher};
+    use std::time::Duration;
     // Objectives:
     // Abstract - It simulates node behavior in response to messages based on the description of the protocol design. (It doesn’t need to make network connections, use persistent storage, or define message formats.)
     // Deterministic - A given simulator run should always produce identical results on any platform.
@@ -212,8 +257,7 @@ mod tests {
     // Faster-than-real-time - It simulates message transmission times, delays, or message order interleaving directly without using the real clock so that each simulation can run as fast as the host system allows. 
     // Full-network Per-message Causal Ordering - It is possible to simulate the absolute arrival time of every message in the network. For example if node A sends node B message T1, and node C sends node D message T2, it can simulate either T1 arriving and being processed first, or T2 arriving and being processed.
     
-    /// A node that responds to pings with strings
-    
+    /// A node that sends pings of different payload types
     pub struct PingNodeType {
         base: PassiveNode,
     }
@@ -226,6 +270,12 @@ mod tests {
         }
     }
 
+    impl Default for PingNodeType {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
     impl std::fmt::Debug for PingNodeType {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             write!(f, "PingNodeType({})", self.ident())
@@ -234,7 +284,7 @@ mod tests {
 
     #[async_trait]
     impl Node for PingNodeType {
-        fn initialize(&mut self, ident: i32, network: Arc<Mutex<Network>>) {
+        fn initialize(&mut self, ident: i32, network: NetworkHandle) {
             self.base.initialize(ident, network);
         }
 
@@ -242,57 +292,62 @@ mod tests {
             self.base.ident()
         }
 
-        fn network(&self) -> Arc<Mutex<Network>> {
+        fn network(&self) -> &NetworkHandle {
             self.base.network()
         }
 
         async fn run(&self) -> ProcessEffect {
             self.log("RUN", "ping node").await;
-            for i in 0..self.network().lock().await.num_nodes() {
-                match i % 3 {
-                    0 => {
-                        let ping_i = Box::new(Ping::new(i.to_string()));
-                        self.send(i as i32, ping_i.box_clone(), None).await;
-                        sleep(Duration::from_secs(1)).await;
-                        self.send(i as i32, ping_i, None).await;
-                        sleep(Duration::from_secs(2)).await;
-                    },
-                    1 => {
-                        let ping_i = Box::new(Ping::new(i as i32));
-                        self.send(i as i32, ping_i.box_clone(), None).await;
-                        sleep(Duration::from_secs(1)).await;
-                        self.send(i as i32, ping_i, None).await;
-                        sleep(Duration::from_secs(2)).await;
-                    },
-                    _ => {
-                        let ping_i = Box::new(Ping::new(i));
-                        self.send(i as i32, ping_i.box_clone(), None).await;
-                        sleep(Duration::from_secs(1)).await;
-                        self.send(i as i32, ping_i, None).await;
-                        sleep(Duration::from_secs(2)).await;
-                    },
-                }
+            self.on_timer(0).await
+        }
+
+        async fn on_timer(&self, tag: u64) -> ProcessEffect {
+            let num_nodes = self.network().num_nodes() as u64;
+            let (i, repeat) = (tag / 2, tag % 2);
+            if i < num_nodes {
+                let ping_i: Box<dyn Message> = match i % 3 {
+                    0 => Box::new(Ping::new(i.to_string())),
+                    1 => Box::new(Ping::new(i as i32)),
+                    _ => Box::new(Ping::new(i)),
+                };
+                self.send(i as i32, ping_i, None).await;
+                let wait = if repeat == 0 { 1 } else { 2 };
+                self.set_timer(wait, tag + 1).await;
             }
             skip().await
         }
 
-        async fn handle(&self, sender: i32, message: Box<dyn Message>) -> ProcessEffect {
-            self.base.handle(sender, message).await
+        async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+            self.base.handle(envelope).await
         }
     }
 
     pub struct PongNodeType {
         base: SequentialNode,
+        costs: ProcessingCosts,
+        router: Router<Box<dyn Message>>,
     }
 
     impl PongNodeType {
         pub fn new() -> Self {
             PongNodeType {
                 base: SequentialNode::new(),
+                costs: ProcessingCosts::new(0)
+                    .with::<Ping<String>>(5)
+                    .with::<Ping<i32>>(5),
+                router: Router::<Box<dyn Message>>::new()
+                    .on::<Ping<String>>(|_, ping| Box::new(Pong::new(ping.payload().clone())))
+                    .on::<Ping<i32>>(|_, ping| Box::new(Pong::new(*ping.payload()))),
             }
         }
     }
 
+    impl Default for PongNodeType {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
     impl std::fmt::Debug for PongNodeType {
         fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             write!(f, "PongNodeType({})", self.ident())
@@ -301,7 +356,7 @@ mod tests {
 
     #[async_trait]
     impl Node for PongNodeType{
-        fn initialize(&mut self, ident: i32, network: Arc<Mutex<Network>>) {
+        fn initialize(&mut self, ident: i32, network: NetworkHandle) {
             self.base.initialize(ident, network);
         }
 
@@ -309,23 +364,28 @@ mod tests {
             self.base.ident()
         }
 
-        fn network(&self) -> Arc<Mutex<Network>> {
+        fn network(&self) -> &NetworkHandle {
             self.base.network()
         }
 
-        async fn handle(&self, sender: i32, message: Box<dyn Message>) -> ProcessEffect {
-            if let Some(ping) = message.as_any_ref().downcast_ref::<Ping<String>>() {
-                sleep(Duration::from_secs(5)).await;
-                let pong = String::default().handle_ping(ping.payload().as_any_ref());
-                self.send(sender, pong, None).await;
-            } else if let Some(ping) = message.as_any_ref().downcast_ref::<Ping<i32>>() {
-                sleep(Duration::from_secs(5)).await;
-                let pong = 0i32.handle_ping(ping.payload().as_any_ref());
-                self.send(sender, pong, None).await;
-            } else {
-                self.base.handle(sender, message).await;
+        fn is_sequential(&self) -> bool {
+            self.base.is_sequential()
+        }
+
+        fn processing_time(&self, message: &dyn Message) -> Time {
+            self.costs.cost(message)
+        }
+
+        async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+            let sender = envelope.sender;
+            match self.router.route(sender, envelope.message) {
+                Ok(pong) => self.send(sender, pong, None).await,
+                Err(unhandled) => {
+                    self.log("unhandled", &unhandled.to_string()).await;
+                    let message = unhandled.0;
+                    self.base.handle(Envelope { message, ..envelope }).await
+                }
             }
-            skip().await
         }
 
         async fn run(&self) -> ProcessEffect {
@@ -334,65 +394,311 @@ mod tests {
         }
     }
 
-    trait PingHandler {
-        fn handle_ping(&self, payload: &dyn Any) -> Box<dyn Message>;
+    /// Votes for a notarization by sending its ident to the collector
+    #[derive(Debug, Default)]
+    struct VoterNode {
+        base: PassiveNode,
     }
-    
-    // Implement for different types
-    impl PingHandler for String {
-        fn handle_ping(&self, payload: &dyn Any) -> Box<dyn Message> {
-            if let Some(p) = payload.downcast_ref::<String>() {
-                Box::new(Pong::new(p.clone()))
-            } else {
-                panic!("Invalid payload type")
+
+    #[async_trait]
+    impl Node for VoterNode {
+        fn initialize(&mut self, ident: i32, network: NetworkHandle) {
+            self.base.initialize(ident, network);
+        }
+
+        fn ident(&self) -> i32 {
+            self.base.ident()
+        }
+
+        fn network(&self) -> &NetworkHandle {
+            self.base.network()
+        }
+
+        fn state_hash(&self) -> Option<u64> {
+            Some(0)
+        }
+
+        async fn run(&self) -> ProcessEffect {
+            self.send(COLLECTOR, Box::new(Ping::new(self.ident())), None).await
+        }
+
+        async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+            self.base.handle(envelope).await
+        }
+    }
+
+    const COLLECTOR: i32 = 3;
+
+    /// Asks for a block by height
+    #[derive(Debug, Clone)]
+    struct GetBlock(u64);
+
+    /// Serves blocks, but only answers every second request
+    #[derive(Debug, Default)]
+    struct BlockServer {
+        base: PassiveNode,
+        requests: std::sync::Mutex<u64>,
+    }
+
+    #[async_trait]
+    impl Node for BlockServer {
+        fn initialize(&mut self, ident: i32, network: NetworkHandle) {
+            self.base.initialize(ident, network);
+        }
+
+        fn ident(&self) -> i32 {
+            self.base.ident()
+        }
+
+        fn network(&self) -> &NetworkHandle {
+            self.base.network()
+        }
+
+        async fn run(&self) -> ProcessEffect {
+            skip().await
+        }
+
+        async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+            let served = network::rpc::serve(self, envelope, |_sender, GetBlock(height)| {
+                let mut requests = self.requests.lock().unwrap();
+                *requests += 1;
+                requests.is_multiple_of(2).then(|| format!("block {}", height))
+            });
+            if let Err(envelope) = served.await {
+                self.base.handle(envelope).await;
             }
+            skip().await
         }
     }
-    
-    impl PingHandler for i32 {
-        fn handle_ping(&self, payload: &dyn Any) -> Box<dyn Message> {
-            if let Some(p) = payload.downcast_ref::<i32>() {
-                Box::new(Pong::new(*p))
-            } else {
-                panic!("Invalid payload type")
+
+    /// Fetches block 7 from nodes 1 and 2 when it starts, and probes
+    /// `probe_target` with a second client
+    #[derive(Debug)]
+    struct FetchNode {
+        base: PassiveNode,
+        rpc: RpcClient<GetBlock, String>,
+        probe: RpcClient<GetBlock, String>,
+        probe_target: i32,
+        replies: std::sync::Mutex<Vec<RpcReply<String>>>,
+    }
+
+    #[async_trait]
+    impl Node for FetchNode {
+        fn initialize(&mut self, ident: i32, network: NetworkHandle) {
+            self.base.initialize(ident, network);
+        }
+
+        fn ident(&self) -> i32 {
+            self.base.ident()
+        }
+
+        fn network(&self) -> &NetworkHandle {
+            self.base.network()
+        }
+
+        async fn run(&self) -> ProcessEffect {
+            self.rpc.call(self, 1, GetBlock(7)).await;
+            self.rpc.call(self, 2, GetBlock(7)).await;
+            self.probe.call(self, self.probe_target, GetBlock(0)).await;
+            skip().await
+        }
+
+        async fn on_timer(&self, tag: u64) -> ProcessEffect {
+            if let Some(reply) = self.rpc.on_timer(self, tag).await {
+                self.replies.lock().unwrap().push(reply);
+            }
+            if let Some(reply) = self.probe.on_timer(self, tag).await {
+                self.replies.lock().unwrap().push(reply);
+            }
+            skip().await
+        }
+
+        async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+            let envelope = match self.rpc.receive_response(self, envelope).await {
+                Ok(reply) => Ok(reply),
+                Err(envelope) => self.probe.receive_response(self, envelope).await,
+            };
+            match envelope {
+                Ok(Some(reply)) => self.replies.lock().unwrap().push(reply),
+                Ok(None) => {}
+                Err(envelope) => return self.base.handle(envelope).await,
             }
+            skip().await
         }
     }
-    
-    #[tokio::test]
-    async fn test_abstract_behavior() {
-        env_logger::init();
-        let network: Arc<tokio::sync::Mutex<Network>> = Network::new(None, 4, Box::new(PrintLogger::default()));
+
+    /// Floods every message it receives, remembering how far it travelled
+    #[derive(Debug, Default)]
+    struct FloodNode {
+        base: PassiveNode,
+        flooding: Flooding,
+        hops: std::sync::Mutex<Option<u32>>,
+    }
+
+    #[async_trait]
+    impl Node for FloodNode {
+        fn initialize(&mut self, ident: i32, network: NetworkHandle) {
+            self.base.initialize(ident, network);
+        }
+
+        fn ident(&self) -> i32 {
+            self.base.ident()
+        }
+
+        fn network(&self) -> &NetworkHandle {
+            self.base.network()
+        }
+
+        async fn run(&self) -> ProcessEffect {
+            skip().await
+        }
+
+        async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+            let hops = envelope.hops;
+            if self.flooding.relay(self, envelope).await {
+                *self.hops.lock().unwrap() = Some(hops);
+            }
+            skip().await
+        }
+    }
+
+    /// Collects votes and notarizes with the first two it receives
+    #[derive(Debug, Default)]
+    struct CollectorNode {
+        base: PassiveNode,
+        votes: std::sync::Mutex<(BTreeSet<i32>, Option<BTreeSet<i32>>)>,
+        envelopes: std::sync::Mutex<Vec<Envelope>>,
+    }
+
+    impl CollectorNode {
+        fn notarization(&self) -> Option<BTreeSet<i32>> {
+            self.votes.lock().unwrap().1.clone()
+        }
+
+        fn envelopes(&self) -> Vec<Envelope> {
+            self.envelopes.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl Node for CollectorNode {
+        fn initialize(&mut self, ident: i32, network: NetworkHandle) {
+            self.base.initialize(ident, network);
+        }
+
+        fn ident(&self) -> i32 {
+            self.base.ident()
+        }
+
+        fn network(&self) -> &NetworkHandle {
+            self.base.network()
+        }
+
+        fn state_hash(&self) -> Option<u64> {
+            let mut hasher = DefaultHasher::new();
+            self.votes.lock().unwrap().hash(&mut hasher);
+            Some(hasher.finish())
+        }
+
+        async fn run(&self) -> ProcessEffect {
+            skip().await
+        }
+
+        async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+            {
+                let mut votes = self.votes.lock().unwrap();
+                votes.0.insert(envelope.sender);
+                if votes.0.len() >= 2 && votes.1.is_none() {
+                    votes.1 = Some(votes.0.clone());
+                }
+            }
+            self.envelopes.lock().unwrap().push(envelope);
+            skip().await
+        }
+    }
+
+    async fn voting_network() -> Simulation {
+        let simulation = Simulation::new(0, Box::new(Constant(1)), Box::new(DebugLogger));
+        for _ in 0..3 {
+            simulation.add_node(Arc::new(VoterNode::default())).await;
+        }
+        simulation.add_node(Arc::new(CollectorNode::default())).await;
+        simulation.start_all_nodes().await;
+        simulation
+    }
+
+    fn collector(network: &Network) -> &CollectorNode {
+        let collector = network.node(COLLECTOR).unwrap().as_ref() as &dyn Any;
+        collector.downcast_ref::<CollectorNode>().unwrap()
+    }
+
+    fn notarization(network: &Network) -> Option<BTreeSet<i32>> {
+        collector(network).notarization()
+    }
+
+    /// Runs the ping/pong scenario with the given seed and logger
+    async fn run_abstract_scenario(seed: u64, logger: Box<dyn Logger>) -> RunSummary {
+        let simulation = abstract_scenario(seed, logger).await;
+        // Process events in virtual time until the network goes quiet
+        simulation.run_to_completion(RunLimits::new()).await
+    }
+
+    async fn abstract_scenario(seed: u64, logger: Box<dyn Logger>) -> Simulation {
+        let simulation = Simulation::new(seed, Box::new(Constant(4)), logger);
         // Add 10 PongNodes
         for _ in 0..10 {
-            network.lock().await.add_node(Arc::new(PongNodeType::new()));
+            simulation.add_node(Arc::new(PongNodeType::new())).await;
         }
 
         // Add PingNode
-        network.lock().await.add_node(Arc::new(PingNodeType::new()));
+        simulation.add_node(Arc::new(PingNodeType::new())).await;
         // Start all nodes
-        network.lock().await.start_all_nodes().await;
+        simulation.start_all_nodes().await;
+        simulation
+    }
 
-        // Run for 10 seconds
-        // TODO: Make shutdown more graceful
-        tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+    #[tokio::test]
+    async fn test_abstract_behavior() {
+        env_logger::init();
+        let summary = run_abstract_scenario(0, Box::new(PrintLogger)).await;
+        assert_eq!(summary.stop_reason, StopReason::Quiescent);
+        // 22 pings, and a pair of pongs from each node that understood its pings
+        assert_eq!(summary.messages_sent, 36);
+        assert_eq!(summary.messages_delivered, 36);
+        // The last pong node handles its pings at 36 and 41
+        assert_eq!(summary.final_time, 45);
     }
 
-    // Test that the simulation is deterministic is done in the scripts directory
+    #[tokio::test]
+    async fn test_deterministic() {
+        // Same seed, byte-identical logs
+        let first = RecordingLogger::new();
+        let second = RecordingLogger::new();
+        run_abstract_scenario(7, Box::new(first.clone())).await;
+        run_abstract_scenario(7, Box::new(second.clone())).await;
+        assert_eq!(first.lines(), second.lines());
+
+        // A different seed interleaves simultaneous events differently
+        let other = RecordingLogger::new();
+        run_abstract_scenario(8, Box::new(other.clone())).await;
+        assert_ne!(first.lines(), other.lines());
+    }
 
     #[tokio::test] 
     async fn test_network_wide_simulation() {
         // Verify simulation runs across all nodes
-        let network = Network::new(None, 4, Box::new(DebugLogger::default()));
+        let simulation = Simulation::new(0, Box::new(Constant(4)), Box::new(DebugLogger));
         
         // Add multiple nodes
         for _ in 0..5 {
-            network.lock().await.add_node(Arc::new(PongNode::new()));
+            simulation.add_node(Arc::new(PongNode::new())).await;
         }
 
-        assert_eq!(network.lock().await.num_nodes(), 5);
-        network.lock().await.start_all_nodes().await;
-        tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+        assert_eq!(simulation.network().lock().await.num_nodes(), 5);
+        simulation.start_all_nodes().await;
+        let summary = simulation.run_to_completion(RunLimits::new()).await;
+        assert_eq!(summary.stop_reason, StopReason::Quiescent);
+        assert_eq!(summary.events_processed, 5);
     }
 
     #[tokio::test]
@@ -400,13 +706,15 @@ mod tests {
         // Verify simulated delays don't use real clock time
         let start = std::time::Instant::now();
         
-        let network = Network::new(None, 4, Box::new(DebugLogger::default()));
-        let pong_node = Arc::new(PongNode::new());
-        network.lock().await.add_node(pong_node.clone());
+        let simulation = Simulation::new(0, Box::new(Constant(4)), Box::new(DebugLogger));
+        simulation.add_node(Arc::new(PongNode::new())).await;
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
 
-        // Handle message with 5 second simulated delay
+        // Ping with a 5 unit simulated delay, handled in 5 units of processing
+        // time and answered after another 4 units
         let ping = Box::new(Ping::new(42));
-        pong_node.handle(0, ping).await;
+        simulation.network().lock().await.send(1, 0, ping, Some(5)).await;
+        assert_eq!(simulation.run().await, 14);
 
         // Should complete much faster than 5 seconds
         assert!(start.elapsed() < Duration::from_secs(5));
@@ -415,22 +723,795 @@ mod tests {
     #[tokio::test]
     async fn test_message_ordering() {
         // Test different message arrival orderings
-        let network = Network::new(None, 4, Box::new(DebugLogger::default()));
-        
-        let node1 = Arc::new(PongNode::new());
-        let node2 = Arc::new(PongNode::new());
-        network.lock().await.add_node(node1.clone());
-        network.lock().await.add_node(node2.clone());
-
-        // Send messages with different delays
-        let ping1 = Box::new(Ping::new(1));
-        let ping2 = Box::new(Ping::new(2));
-
-        // Messages can be processed in either order
-        tokio::join!(
-            node1.handle(0, ping1),
-            node2.handle(0, ping2)
+        for (delay1, delay2) in [(1, 2), (2, 1)] {
+            let simulation = Simulation::new(0, Box::new(Constant(4)), Box::new(DebugLogger));
+            simulation.add_node(Arc::new(PongNode::new())).await;
+            simulation.add_node(Arc::new(PongNode::new())).await;
+            simulation.add_node(Arc::new(PassiveNode::new())).await;
+            let network = simulation.network();
+
+            // Send messages with different delays
+            let ping1 = Box::new(Ping::new(1));
+            let ping2 = Box::new(Ping::new(2));
+            network.lock().await.send(2, 0, ping1, Some(delay1)).await;
+            network.lock().await.send(2, 1, ping2, Some(delay2)).await;
+
+            // Only the earlier ping has arrived, and its pong is in flight
+            simulation.run_until(1).await;
+            assert_eq!(network.lock().await.pending_events(), 2);
+
+            simulation.run().await;
+            assert_eq!(network.lock().await.pending_events(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fault_injection() {
+        let logger = RecordingLogger::new();
+        let simulation = Simulation::new(0, Box::new(Constant(4)), Box::new(logger.clone()));
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+
+        // Everything from 0 to 1 is lost, everything from 1 to 0 arrives twice
+        let mut faults = FaultConfig::default();
+        faults.set_link(0, 1, LinkFaults { drop_probability: 1.0, ..LinkFaults::default() });
+        faults.set_link(1, 0, LinkFaults { duplicate_probability: 1.0, ..LinkFaults::default() });
+        let network = simulation.network();
+        network.lock().await.set_faults(faults);
+
+        network.lock().await.send(0, 1, Box::new(Ping::new(1)), None).await;
+        network.lock().await.send(1, 0, Box::new(Ping::new(2)), None).await;
+        simulation.run().await;
+
+        let count = |event: &str| {
+            let column = format!("| {:10} |", event);
+            logger.lines().iter().filter(|line| line.contains(&column)).count()
+        };
+        assert_eq!(count("drop"), 1);
+        assert_eq!(count("duplicate"), 1);
+        assert_eq!(count("receive"), 2);
+
+        // Messages over a link with a reorder bound arrive out of send order,
+        // in the same order on every run with the same seed
+        async fn reordered(seed: u64) -> Vec<u64> {
+            let logger = RecordingLogger::new();
+            let simulation = Simulation::new(seed, Box::new(Constant(4)), Box::new(logger.clone()));
+            simulation.add_node(Arc::new(PassiveNode::new())).await;
+            simulation.add_node(Arc::new(PassiveNode::new())).await;
+            let mut faults = FaultConfig::default();
+            faults.set_link(0, 1, LinkFaults { reorder_bound: 10, ..LinkFaults::default() });
+            let network = simulation.network();
+            network.lock().await.set_faults(faults);
+            for payload in 0..8u64 {
+                network.lock().await.send(0, 1, Box::new(Ping::new(payload)), None).await;
+            }
+            simulation.run().await;
+            logger
+                .lines()
+                .iter()
+                .filter(|line| line.contains("| RECEIVE    |"))
+                .filter_map(|line| line.rsplit("payload: ").next()?.split(' ').next()?.parse().ok())
+                .collect()
+        }
+        let order = reordered(0).await;
+        assert_eq!(order.len(), 8);
+        assert!(order.windows(2).any(|pair| pair[0] > pair[1]));
+        assert_eq!(order, reordered(0).await);
+
+        // Reordering a message whose latency reaches the end of time leaves
+        // it due at the end of time
+        let simulation = Simulation::new(0, Box::new(Constant(Time::MAX)), Box::new(DebugLogger));
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        let mut faults = FaultConfig::default();
+        faults.set_link(0, 1, LinkFaults { reorder_bound: 5, ..LinkFaults::default() });
+        let network = simulation.network();
+        network.lock().await.set_faults(faults);
+        network.lock().await.send(0, 1, Box::new(Ping::new(1)), None).await;
+        assert_eq!(simulation.run().await, Time::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_partition_heal() {
+        let logger = RecordingLogger::new();
+        let simulation = Simulation::new(0, Box::new(Constant(4)), Box::new(logger.clone()));
+        for _ in 0..4 {
+            simulation.add_node(Arc::new(PassiveNode::new())).await;
+        }
+        let network = simulation.network();
+        let schedule: PartitionSchedule = "t=10 split {0..1}|{2..3} hold; t=50 heal".parse().unwrap();
+        network.lock().await.schedule_partitions(&schedule);
+
+        // During the split, only the message within the partition gets through
+        simulation.run_until(12).await;
+        network.lock().await.send(0, 1, Box::new(Ping::new(1)), None).await;
+        network.lock().await.send(0, 2, Box::new(Ping::new(2)), None).await;
+        simulation.run_until(49).await;
+        let received = |lines: &[String]| lines.iter().filter(|line| line.contains("| RECEIVE    |")).count();
+        assert_eq!(received(&logger.lines()), 1);
+
+        // The held message is delivered when the partition heals
+        assert_eq!(simulation.run().await, 50);
+        assert_eq!(received(&logger.lines()), 2);
+    }
+
+    #[tokio::test]
+    async fn test_timers() {
+        let logger = RecordingLogger::new();
+        let simulation = Simulation::new(0, Box::new(Constant(4)), Box::new(logger.clone()));
+        simulation.add_node(Arc::new(PongNode::new())).await;
+        simulation.add_node(Arc::new(PongNode::new())).await;
+        simulation.add_node(Arc::new(PingNode::new())).await;
+        simulation.start_all_nodes().await;
+
+        // Pings leave at 0, 1, 3, 4, 6 and 7. Node 1 gets its pings at 7 and 8,
+        // handles them at 12 and 17, and the last pong arrives at 21.
+        assert_eq!(simulation.run().await, 21);
+
+        // A cancelled timer never fires
+        let network = simulation.network();
+        let timer = network.lock().await.set_timer(0, 5, 99);
+        network.lock().await.cancel_timer(timer);
+        simulation.run().await;
+        assert!(!logger.lines().iter().any(|line| line.contains("tag 99")));
+    }
+
+    #[tokio::test]
+    async fn test_sequential_mailbox() {
+        let logger = RecordingLogger::new();
+        let simulation = Simulation::new(0, Box::new(Constant(4)), Box::new(logger.clone()));
+        simulation.add_node(Arc::new(PongNode::new())).await;
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        simulation.start_all_nodes().await;
+
+        // Three pings arrive at once; the pong node handles them one at a time,
+        // 5 units each, while the rest wait in its mailbox
+        let network = simulation.network();
+        for i in 0..3 {
+            network.lock().await.send(1, 0, Box::new(Ping::new(i)), None).await;
+        }
+        simulation.run_until(4).await;
+        assert!(network.lock().await.is_busy(0));
+        assert_eq!(network.lock().await.mailbox_len(0), 3);
+        simulation.run_until(9).await;
+        assert_eq!(network.lock().await.mailbox_len(0), 2);
+
+        // Handled at 9, 14 and 19; once the last reply is in, the run ends
+        assert_eq!(simulation.run().await, 23);
+        assert!(!network.lock().await.is_busy(0));
+        let handled = logger.lines().iter().filter(|line| line.contains("| handle     |")).count();
+        assert_eq!(handled, 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_limits() {
+        let simulation = Simulation::new(0, Box::new(Constant(4)), Box::new(DebugLogger));
+        simulation.add_node(Arc::new(PongNode::new())).await;
+        simulation.add_node(Arc::new(PingNode::new())).await;
+        simulation.start_all_nodes().await;
+
+        let summary = simulation.run_to_completion(RunLimits::new().max_events(3)).await;
+        assert_eq!(summary.stop_reason, StopReason::MaxEvents);
+        assert_eq!(summary.events_processed, 3);
+
+        let summary = simulation.run_to_completion(RunLimits::new().max_time(5)).await;
+        assert_eq!(summary.stop_reason, StopReason::MaxTime);
+        assert_eq!(summary.final_time, 5);
+
+        let summary = simulation.run_to_completion(RunLimits::new()).await;
+        assert_eq!(summary.stop_reason, StopReason::Quiescent);
+        assert!(simulation.network().lock().await.is_quiescent());
+    }
+
+    #[tokio::test]
+    async fn test_trace_replay() {
+        let recorded = RecordingLogger::new();
+        let simulation = abstract_scenario(7, Box::new(recorded.clone())).await;
+        simulation.network().lock().await.record_trace();
+        simulation.run_to_completion(RunLimits::new()).await;
+        let trace = simulation.network().lock().await.take_trace().unwrap();
+        assert!(trace.entries().iter().any(|entry| entry.kind == TraceKind::Send));
+        assert!(trace.entries().iter().any(|entry| entry.kind == TraceKind::Timer));
+
+        let path = std::env::temp_dir().join(format!("simtfl-trace-{}.txt", std::process::id()));
+        trace.save(&path).unwrap();
+        let loaded = Trace::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, trace);
+
+        // Replaying under another seed forces the recorded interleaving
+        let replayed = RecordingLogger::new();
+        let simulation = abstract_scenario(8, Box::new(replayed.clone())).await;
+        simulation.network().lock().await.replay(&loaded);
+        simulation.run_to_completion(RunLimits::new()).await;
+        assert_eq!(replayed.lines(), recorded.lines());
+    }
+
+    #[tokio::test]
+    async fn test_controlled_interleaving() {
+        // Node 0 sends m_a to node 1 and node 2 sends m_c to node 3; both
+        // arrive at the same virtual time, and the strategy picks which first
+        for first_sender in [0, 2] {
+            let logger = RecordingLogger::new();
+            let simulation = Simulation::new(0, Box::new(Constant(4)), Box::new(logger.clone()));
+            for _ in 0..4 {
+                simulation.add_node(Arc::new(PassiveNode::new())).await;
+            }
+            simulation
+                .set_strategy(Box::new(CallbackStrategy::new(move |candidates: &[Candidate]| {
+                    candidates
+                        .iter()
+                        .position(|candidate| candidate.sender == first_sender)
+                        .unwrap_or(0)
+                })))
+                .await;
+            let network = simulation.network();
+            network.lock().await.send(0, 1, Box::new(Ping::new(1)), None).await;
+            network.lock().await.send(2, 3, Box::new(Ping::new(2)), None).await;
+            simulation.run().await;
+
+            let receivers: Vec<String> = logger
+                .lines()
+                .into_iter()
+                .filter(|line| line.contains("receive"))
+                .collect();
+            assert_eq!(receivers.len(), 2);
+            assert!(receivers[0].contains(&format!("from {:2}", first_sender)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_exhaustive_exploration() {
+        // Notarizing with any two votes is fine in every interleaving
+        let mut explorer = Explorer::new(voting_network, |network: &Network| {
+            match notarization(network) {
+                Some(votes) if votes.len() < 2 => Err(format!("notarized with {:?}", votes)),
+                _ => Ok(()),
+            }
+        });
+        let report = explorer.explore().await;
+        assert!(report.violation.is_none());
+        assert!(report.executions > 0);
+        // Votes arriving in either order lead to the same state
+        assert!(report.pruned > 0);
+
+        // With random latencies the order of sends changes the latencies
+        // drawn, so nothing is pruned and more interleavings are followed
+        let random_network = || async {
+            let simulation = voting_network().await;
+            simulation.network().lock().await.set_latency_model(Box::new(Uniform::new(1, 3)));
+            simulation
+        };
+        let mut explorer = Explorer::new(random_network, |_: &Network| Ok(()));
+        let random = explorer.explore().await;
+        assert_eq!(random.pruned, 0);
+        assert!(random.executions > report.executions);
+
+        // Expecting voter 0 in every notarization fails when 1 and 2 vote first
+        let mut explorer = Explorer::new(voting_network, |network: &Network| {
+            match notarization(network) {
+                Some(votes) if !votes.contains(&0) => Err(format!("notarized with {:?}", votes)),
+                _ => Ok(()),
+            }
+        });
+        let violation = explorer.explore().await.violation.unwrap();
+        assert_eq!(violation.message, "notarized with {1, 2}");
+
+        // The trace replays to the violating state
+        let simulation = voting_network().await;
+        simulation.network().lock().await.replay(&violation.trace);
+        let limits = RunLimits::new().max_events(violation.depth as u64);
+        simulation.run_to_completion(limits).await;
+        let votes = notarization(&*simulation.network().lock().await);
+        assert_eq!(votes, Some(BTreeSet::from([1, 2])));
+    }
+
+    #[tokio::test]
+    async fn test_invariant_hooks() {
+        let simulation = voting_network().await;
+        simulation
+            .add_invariant("notarized", |network: &Network| {
+                match notarization(network) {
+                    Some(votes) if votes.len() < 2 => Err(format!("notarized with {:?}", votes)),
+                    _ => Ok(()),
+                }
+            })
+            .await;
+        simulation
+            .add_invariant("at most two votes", |network: &Network| {
+                let collector = network.node(COLLECTOR).unwrap().as_ref() as &dyn Any;
+                let collector = collector.downcast_ref::<CollectorNode>().unwrap();
+                match collector.votes.lock().unwrap().0.len() {
+                    0..=2 => Ok(()),
+                    votes => Err(format!("{} votes", votes)),
+                }
+            })
+            .await;
+
+        // The third vote breaks the second invariant and stops the run
+        let summary = simulation.run_to_completion(RunLimits::new()).await;
+        assert_eq!(summary.stop_reason, StopReason::InvariantViolated);
+        let network = simulation.network();
+        let network = network.lock().await;
+        let violation = network.violation().unwrap();
+        assert_eq!(violation.invariant, "at most two votes");
+        assert_eq!(violation.message, "3 votes");
+        assert_eq!(violation.event.target, COLLECTOR);
+
+        // The trace prefix ends with the offending delivery
+        let last = violation.trace.entries().last().unwrap();
+        assert_eq!(last.kind, TraceKind::Deliver);
+        assert_eq!(last.seq, Some(violation.event.seq));
+        assert_eq!(summary.messages_delivered, 3);
+    }
+
+    #[tokio::test]
+    async fn test_crash_recovery() {
+        // The collector crashes at t=0, before the votes arrive at t=1
+        let simulation = voting_network().await;
+        let network = simulation.network();
+        network.lock().await.schedule_crash(0, COLLECTOR);
+        simulation.run_until(1).await;
+        assert!(network.lock().await.is_crashed(COLLECTOR));
+        assert_eq!(network.lock().await.stats().messages_dropped, 3);
+        assert_eq!(notarization(&*network.lock().await), None);
+
+        // Back with its persisted state, it receives votes again
+        network.lock().await.recover(COLLECTOR, Restart::Persisted);
+        network.lock().await.send(0, COLLECTOR, Box::new(Ping::new(0)), None).await;
+        network.lock().await.send(1, COLLECTOR, Box::new(Ping::new(1)), None).await;
+        simulation.run().await;
+        assert!(!network.lock().await.is_crashed(COLLECTOR));
+        assert_eq!(notarization(&*network.lock().await), Some(BTreeSet::from([0, 1])));
+
+        // Restarted from scratch, it has lost its notarization
+        network.lock().await.crash(COLLECTOR);
+        let fresh = Box::new(CollectorNode::default());
+        network.lock().await.recover(COLLECTOR, Restart::Fresh(fresh));
+        simulation.run().await;
+        assert_eq!(notarization(&*network.lock().await), None);
+        let restarted = network.lock().await.node(COLLECTOR).unwrap().clone();
+        assert_eq!(restarted.ident(), COLLECTOR);
+
+        // A crashed node's timers never fire
+        let logger = RecordingLogger::new();
+        let simulation = Simulation::new(0, Box::new(Constant(4)), Box::new(logger.clone()));
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        simulation.add_node(Arc::new(PingNodeType::new())).await;
+        simulation.start_all_nodes().await;
+        simulation.network().lock().await.schedule_crash(2, 1);
+        simulation.run().await;
+        let timers = logger.lines().iter().filter(|line| line.contains("timer")).count();
+        // Fired at 1 only; the one due at 3 was discarded
+        assert_eq!(timers, 1);
+    }
+
+    #[tokio::test]
+    async fn test_byzantine_node() {
+        // Node 3 runs the honest ping code, but is silent towards node 1,
+        // forges what it sends to node 2 and holds back string pings
+        let adversary: Vec<Box<dyn Adversary>> = vec![
+            Box::new(Silence::towards([1])),
+            Box::new(Equivocate::new(|target, _message: &dyn Message| {
+                (target == 2).then(|| Box::new(Ping::new("forged".to_string())) as Box<dyn Message>)
+            })),
+            Box::new(Delay::new(0).with::<Ping<String>>(10)),
+        ];
+        let logger = RecordingLogger::new();
+        let simulation = Simulation::new(0, Box::new(Constant(4)), Box::new(logger.clone()));
+        for _ in 0..3 {
+            simulation.add_node(Arc::new(PassiveNode::new())).await;
+        }
+        let byzantine = ByzantineNode::new(PingNodeType::new(), adversary);
+        simulation.add_node(Arc::new(byzantine)).await;
+        simulation.start_all_nodes().await;
+        simulation.run().await;
+
+        let received = |ident: i32| -> Vec<String> {
+            logger
+                .lines()
+                .into_iter()
+                .filter(|line| line.contains(&format!("| {:4} | receive", ident)))
+                .collect()
+        };
+        assert!(received(1).is_empty());
+        let forged = received(2);
+        assert_eq!(forged.len(), 2);
+        assert!(forged.iter().all(|line| line.contains("forged")));
+        // Sent at 0 and 1, arriving 10 later than the latency of 4
+        let delayed = received(0);
+        assert_eq!(delayed.len(), 2);
+        assert!(delayed[0].starts_with("   14 |"));
+        assert!(delayed[1].starts_with("   15 |"));
+    }
+
+    #[tokio::test]
+    async fn test_partial_synchrony() {
+        let simulation = Simulation::new(0, Box::new(Constant(1)), Box::new(DebugLogger));
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        let network = simulation.network();
+        let model = PartialSynchrony::new(20, 3);
+        network.lock().await.set_delivery_adversary(Box::new(MaxDelay), model);
+
+        // Before GST the adversary holds the message until GST + delta
+        network.lock().await.send(0, 1, Box::new(Ping::new(1)), None).await;
+        simulation.run_until(22).await;
+        assert_eq!(network.lock().await.stats().messages_delivered, 0);
+        assert_eq!(simulation.run().await, 23);
+
+        // After GST every message arrives within delta
+        simulation.run_until(30).await;
+        network.lock().await.send(0, 1, Box::new(Ping::new(2)), None).await;
+        assert_eq!(simulation.run().await, 33);
+
+        // An adversary can pick any delay within the bound, e.g. to reorder
+        let adversary = |_now, sender: i32, _target, _message: &dyn Message, _proposed, max| {
+            if sender == 0 { max } else { 0 }
+        };
+        network.lock().await.set_delivery_adversary(Box::new(adversary), model);
+        network.lock().await.send(0, 1, Box::new(Ping::new(3)), None).await;
+        network.lock().await.send(1, 0, Box::new(Ping::new(4)), None).await;
+        simulation.run_until(33).await;
+        assert_eq!(network.lock().await.stats().messages_delivered, 3);
+        assert_eq!(simulation.run().await, 36);
+
+        // The adversary only delays messages once their bytes have left the
+        // link: 1000 bytes at 100 per unit leave at 46 and arrive by 46 + delta
+        network.lock().await.set_bandwidth(Bandwidth::new(100));
+        let sizes = MessageSizes::new().with::<MessageString>(|text| text.message.len());
+        network.lock().await.set_message_sizes(sizes);
+        let block = MessageString::new("x".repeat(1000));
+        network.lock().await.send(0, 1, Box::new(block.clone()), None).await;
+        network.lock().await.send(1, 0, Box::new(block), None).await;
+        simulation.run_until(45).await;
+        assert_eq!(network.lock().await.stats().messages_delivered, 4);
+        simulation.run_until(46).await;
+        assert_eq!(network.lock().await.stats().messages_delivered, 5);
+        assert_eq!(simulation.run().await, 49);
+
+        // If GST never arrives, the adversary may hold messages until the end
+        // of time
+        network.lock().await.set_delivery_adversary(Box::new(MaxDelay), PartialSynchrony::new(Time::MAX, 3));
+        network.lock().await.send(0, 1, Box::new(Ping::new(5)), None).await;
+        assert_eq!(simulation.run().await, Time::MAX);
+
+        // A latency at the end of time stays there after the link's transfer
+        let simulation = Simulation::new(0, Box::new(Constant(Time::MAX)), Box::new(DebugLogger));
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        let network = simulation.network();
+        network.lock().await.set_bandwidth(Bandwidth::new(1));
+        network.lock().await.send(0, 1, Box::new(Ping::new(6)), None).await;
+        assert_eq!(simulation.run().await, Time::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_envelope_metadata() {
+        let simulation = voting_network().await;
+        let network = simulation.network();
+        simulation.run_until(5).await;
+        // From now on everything node 1 sends arrives twice
+        let mut faults = FaultConfig::default();
+        faults.set_link(1, COLLECTOR, LinkFaults { duplicate_probability: 1.0, ..LinkFaults::default() });
+        network.lock().await.set_faults(faults);
+        network.lock().await.send(1, COLLECTOR, Box::new(Ping::new(1)), None).await;
+        simulation.run().await;
+
+        let network = network.lock().await;
+        let envelopes = collector(&network).envelopes();
+        assert_eq!(envelopes.len(), 5);
+        for envelope in &envelopes[..3] {
+            assert_eq!((envelope.sent_at, envelope.delivered_at), (0, 1));
+            assert_eq!(envelope.hops, 1);
+            assert!(envelope.downcast_ref::<Ping<i32>>().is_some());
+        }
+        // Ids are unique per send, and copies of a duplicated message share one
+        let mut ids: Vec<u64> = envelopes[..3].iter().map(|envelope| envelope.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 2]);
+        assert_eq!(envelopes[3].id, 3);
+        assert_eq!(envelopes[4].id, 3);
+        assert_eq!((envelopes[4].sender, envelopes[4].sent_at, envelopes[4].delivered_at), (1, 5, 6));
+    }
+
+    #[tokio::test]
+    async fn test_happens_before() {
+        let simulation = voting_network().await;
+        let network = simulation.network();
+        network.lock().await.record_causality();
+        simulation.run().await;
+        // Once it has all votes, the collector tells voter 0
+        network.lock().await.send(COLLECTOR, 0, Box::new(Ping::new(3)), None).await;
+        simulation.run().await;
+
+        let network = network.lock().await;
+        let clock = network.clock(COLLECTOR);
+        assert_eq!((0..4).map(|ident| clock.get(ident)).collect::<Vec<_>>(), vec![1, 1, 1, 4]);
+        let causality = network.causality().unwrap();
+        assert_eq!(causality.events().len(), 8);
+        // The votes were sent independently of each other, but all of them
+        // before the collector's message
+        assert_eq!(causality.concurrent_messages(), vec![(0, 1), (0, 2), (1, 2)]);
+        let last = causality.send_of(3).unwrap();
+        for vote in 0..3 {
+            assert!(causality.happened_before(causality.send_of(vote).unwrap(), last));
+        }
+        assert!(causality.happened_before(last, causality.receive_of(3).unwrap()));
+        assert!(causality.causal_order_violations().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_flooding() {
+        let simulation = Simulation::new(0, Box::new(Constant(1)), Box::new(DebugLogger));
+        for _ in 0..8 {
+            simulation.add_node(Arc::new(FloodNode::default())).await;
+        }
+        simulation.start_all_nodes().await;
+        let network = simulation.network();
+        network.lock().await.set_topology(Topology::ring(8));
+        simulation.run().await;
+
+        // Only neighbors can talk to each other
+        network.lock().await.send(0, 4, Box::new(Ping::new(0)), None).await;
+        assert_eq!(network.lock().await.stats().messages_sent, 0);
+
+        let block = MessageString::new("block 1".to_string());
+        let origin = {
+            let network = network.lock().await;
+            network.node(0).unwrap().clone()
+        };
+        let origin = (origin.as_ref() as &dyn Any).downcast_ref::<FloodNode>().unwrap();
+        origin.flooding.publish(origin, Box::new(block.clone())).await;
+        assert_eq!(simulation.run().await, 5);
+
+        // The block reaches each node along the shortest path around the ring.
+        // Each node relays it once, except to where it came from, and node 4
+        // gets it from both sides.
+        let network = network.lock().await;
+        for ident in 1..8 {
+            let node = network.node(ident).unwrap().as_ref() as &dyn Any;
+            let node = node.downcast_ref::<FloodNode>().unwrap();
+            let distance = ident.min(8 - ident) as u32;
+            assert_eq!(node.flooding.first_seen(&block), Some(distance as Time));
+            assert_eq!(*node.hops.lock().unwrap(), Some(distance));
+        }
+        assert_eq!(network.stats().messages_sent, 9);
+        assert_eq!(network.stats().messages_delivered, 9);
+    }
+
+    #[tokio::test]
+    async fn test_block_propagation() {
+        let simulation = Simulation::new(0, Box::new(Constant(1)), Box::new(DebugLogger));
+        for _ in 0..16 {
+            simulation.add_node(Arc::new(BlockRelayNode::new())).await;
+        }
+        simulation.start_all_nodes().await;
+        let network = simulation.network();
+        let topology = Topology::random_regular(16, 3, network.lock().await.rng());
+        assert!(topology.is_connected());
+        network.lock().await.set_topology(topology.clone());
+        simulation.run().await;
+
+        let nodes: Vec<Arc<dyn Node>> = {
+            let network = network.lock().await;
+            (0..16).map(|ident| network.node(ident).unwrap().clone()).collect()
+        };
+        let nodes: Vec<&BlockRelayNode> = nodes
+            .iter()
+            .map(|node| (node.as_ref() as &dyn Any).downcast_ref::<BlockRelayNode>().unwrap())
+            .collect();
+
+        // A block reaches every node along the shortest paths from its miner,
+        // one hop per time unit, and every node follows it
+        for (height, miner) in [0, 9].into_iter().enumerate() {
+            let mined = simulation.now().await;
+            let block = nodes[miner].mine(network.lock().await.ids()).await;
+            simulation.run().await;
+            let distances = topology.distances(miner as i32);
+            for (ident, node) in nodes.iter().enumerate() {
+                let distance = distances[&(ident as i32)] as Time;
+                assert_eq!(node.arrival(&block), Some(mined + distance));
+                assert_eq!(node.tip().map(|tip| tip.score()), Some(height as i64 + 1));
+            }
+            let eccentricity = *distances.values().max().unwrap() as Time;
+            assert_eq!(BlockRelayNode::propagation_time(&nodes, &block), Some(eccentricity));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bandwidth() {
+        let simulation = Simulation::new(0, Box::new(Constant(1)), Box::new(DebugLogger));
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        let network = simulation.network();
+        network.lock().await.set_bandwidth(Bandwidth::new(100));
+        let sizes = MessageSizes::new().with::<MessageString>(|text| text.message.len());
+        network.lock().await.set_message_sizes(sizes);
+
+        // A full block takes 10 units to transfer, and a compact one sent
+        // right after it waits for it before taking 2 more
+        let full = MessageString::new("x".repeat(1000));
+        let compact = MessageString::new("x".repeat(200));
+        network.lock().await.send(0, 1, Box::new(full), None).await;
+        network.lock().await.send(0, 1, Box::new(compact), None).await;
+        assert_eq!(simulation.run_until(11).await, 11);
+        assert_eq!(network.lock().await.stats().messages_delivered, 1);
+        assert_eq!(simulation.run().await, 13);
+        assert_eq!(network.lock().await.stats().bytes_sent, 1200);
+
+        // The link in the other direction has its own queue
+        network.lock().await.send(0, 1, Box::new(MessageString::new("x".repeat(500))), None).await;
+        network.lock().await.send(1, 0, Box::new(MessageString::new("x".repeat(500))), None).await;
+        assert_eq!(simulation.run().await, 19);
+
+        // Without a bandwidth or sizes, messages are not sized at all
+        let simulation = Simulation::new(0, Box::new(Constant(1)), Box::new(DebugLogger));
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        let network = simulation.network();
+        network.lock().await.send(0, 1, Box::new(MessageString::new("x".repeat(500))), None).await;
+        assert_eq!(simulation.run().await, 1);
+        assert_eq!(network.lock().await.stats().bytes_sent, 0);
+    }
+
+    async fn gossip_ring(config: GossipConfig) -> (Simulation, Vec<Arc<dyn Node>>) {
+        let simulation = Simulation::new(0, Box::new(Constant(1)), Box::new(DebugLogger));
+        for _ in 0..12 {
+            simulation.add_node(Arc::new(GossipNode::<String>::new(config))).await;
+        }
+        let network = simulation.network();
+        network.lock().await.set_topology(Topology::ring(12));
+        simulation.start_all_nodes().await;
+        let network = network.lock().await;
+        let nodes = (0..12).map(|ident| network.node(ident).unwrap().clone()).collect();
+        drop(network);
+        (simulation, nodes)
+    }
+
+    fn as_gossip(node: &Arc<dyn Node>) -> &GossipNode<String> {
+        (node.as_ref() as &dyn Any).downcast_ref().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_gossip() {
+        // Pushed to one neighbor for three hops, a message reaches 4 nodes
+        let (simulation, nodes) = gossip_ring(GossipConfig::new(1).ttl(3)).await;
+        let gossip: Vec<&GossipNode<String>> = nodes.iter().map(as_gossip).collect();
+        let id = gossip[0].publish("block 1".to_string()).await;
+        simulation.run().await;
+        let reached = gossip.iter().filter(|node| node.first_seen(id).is_some()).count();
+        assert_eq!(reached, 4);
+        assert_eq!(GossipNode::coverage_time(&gossip, id), None);
+
+        // Pulling from neighbors fills in the rest of the ring
+        let (simulation, nodes) = gossip_ring(GossipConfig::new(1).ttl(3).pull(5, 20)).await;
+        let gossip: Vec<&GossipNode<String>> = nodes.iter().map(as_gossip).collect();
+        let id = gossip[0].publish("block 1".to_string()).await;
+        simulation.run().await;
+        let coverage = GossipNode::coverage_time(&gossip, id).unwrap();
+        assert!(coverage > 3);
+        let stats = gossip.iter().fold(GossipStats::default(), |total, node| total + node.stats());
+        assert_eq!(stats.delivered, 12);
+        assert!(stats.pulls_sent > 0);
+        assert_eq!(stats.redundancy(), stats.duplicates as f64 / 12.0);
+        // Coverage is measured from the publisher's first sighting
+        assert_eq!(GossipNode::coverage_time(&gossip[1..], id), None);
+
+        // A node forgets when it first saw messages it no longer caches
+        let (simulation, nodes) = gossip_ring(GossipConfig::new(1).ttl(1).cache_size(1)).await;
+        let publisher = as_gossip(&nodes[0]);
+        let first = publisher.publish("block 1".to_string()).await;
+        let second = publisher.publish("block 2".to_string()).await;
+        simulation.run().await;
+        assert_eq!(publisher.first_seen(first), None);
+        assert_eq!(publisher.first_seen(second), Some(0));
+
+        // A node that restarts with its persisted state pulls again, and
+        // catches up on what it missed while it was down
+        let (simulation, nodes) = gossip_ring(GossipConfig::new(1).ttl(3).pull(5, 20)).await;
+        let gossip: Vec<&GossipNode<String>> = nodes.iter().map(as_gossip).collect();
+        let network = simulation.network();
+        network.lock().await.schedule_crash(1, 6);
+        network.lock().await.schedule_recover(50, 6, Restart::Persisted);
+        simulation.run_until(2).await;
+        let id = gossip[0].publish("block 1".to_string()).await;
+        simulation.run().await;
+        assert!(gossip[6].first_seen(id).is_some_and(|time| time > 50));
+    }
+
+    #[tokio::test]
+    async fn test_rpc() {
+        let simulation = Simulation::new(0, Box::new(Constant(1)), Box::new(DebugLogger));
+        let client = FetchNode {
+            base: PassiveNode::new(),
+            rpc: RpcClient::new(10).retries(1),
+            probe: RpcClient::new(3).client(1),
+            probe_target: 2,
+            replies: Default::default(),
+        };
+        simulation.add_node(Arc::new(client)).await;
+        simulation.add_node(Arc::new(BlockServer::default())).await;
+        // Node 2 never answers
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        simulation.start_all_nodes().await;
+        // Node 2 forges the response to the call made to node 1
+        let forged = RpcResponse { client: 0, call: 0, response: "forged".to_string() };
+        simulation.network().lock().await.send(2, 0, Box::new(forged), Some(1)).await;
+
+        // The probe's call, with the same id as the first call, times out at 3
+        // without touching the first call's timer. Node 1 ignores the first
+        // request; the retry at 10 gets the block at 12. Node 2 does not
+        // answer the retry either, so the call times out.
+        assert_eq!(simulation.run().await, 20);
+        let client = simulation.network().lock().await.node(0).unwrap().clone();
+        let client = (client.as_ref() as &dyn Any).downcast_ref::<FetchNode>().unwrap();
+        let replies = client.replies.lock().unwrap().clone();
+        assert_eq!(
+            replies,
+            vec![
+                RpcReply { call: 0, target: 2, attempts: 1, result: Err(RpcError::TimedOut) },
+                RpcReply { call: 0, target: 1, attempts: 2, result: Ok("block 7".to_string()) },
+                RpcReply { call: 1, target: 2, attempts: 2, result: Err(RpcError::TimedOut) },
+            ]
         );
+        assert_eq!(client.rpc.pending() + client.probe.pending(), 0);
+
+        // Two clients calling the same node with the same call id each get
+        // their own response. Node 1 answers the first call and the probe's
+        // retry at 3.
+        let simulation = Simulation::new(0, Box::new(Constant(1)), Box::new(DebugLogger));
+        let client = FetchNode {
+            base: PassiveNode::new(),
+            rpc: RpcClient::new(10).retries(1),
+            probe: RpcClient::new(3).client(1).retries(1),
+            probe_target: 1,
+            replies: Default::default(),
+        };
+        simulation.add_node(Arc::new(client)).await;
+        let server = BlockServer { requests: std::sync::Mutex::new(1), ..BlockServer::default() };
+        simulation.add_node(Arc::new(server)).await;
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        simulation.start_all_nodes().await;
+        simulation.run().await;
+        let client = simulation.network().lock().await.node(0).unwrap().clone();
+        let client = (client.as_ref() as &dyn Any).downcast_ref::<FetchNode>().unwrap();
+        let replies = client.replies.lock().unwrap().clone();
+        assert_eq!(
+            replies,
+            vec![
+                RpcReply { call: 0, target: 1, attempts: 1, result: Ok("block 7".to_string()) },
+                RpcReply { call: 0, target: 1, attempts: 2, result: Ok("block 0".to_string()) },
+                RpcReply { call: 1, target: 2, attempts: 2, result: Err(RpcError::TimedOut) },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_without_lock() {
+        let simulation = Simulation::new(0, Box::new(Constant(1)), Box::new(DebugLogger));
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        simulation.add_node(Arc::new(PassiveNode::new())).await;
+        let network = simulation.network();
+        let node = network.lock().await.node(0).unwrap().clone();
+
+        // Sends and timers go through the node's handle, so they do not wait
+        // for the network lock; they are applied when the network next steps
+        {
+            let network = network.lock().await;
+            node.send(1, Box::new(Ping::new(1)), Some(3)).await;
+            let timer = node.set_timer(5, 1).await;
+            node.cancel_timer(timer).await;
+            assert_eq!(network.stats().messages_sent, 0);
+            assert_eq!(node.network().neighbors(0), vec![1]);
+        }
+
+        let end = simulation.run().await;
+        let stats = network.lock().await.stats();
+        assert_eq!((stats.messages_sent, stats.messages_delivered), (1, 1));
+        // The cancelled timer never fired
+        assert_eq!(stats.events_processed, 1);
+        assert_eq!(node.network().now(), end);
     }
     
 }
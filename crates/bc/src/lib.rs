@@ -1,19 +1,23 @@
 use std::collections::{HashSet, VecDeque};
 
+use utils::IdAllocator;
+
+pub mod relay;
+
+pub use relay::BlockRelayNode;
+
 #[allow(dead_code)]
 /// Unique identifier for block hashes
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
-struct BlockHash(u64);
+pub struct BlockHash(u64);
 
 impl BlockHash {
-    fn new() -> Self {
-        use std::sync::atomic::{AtomicU64, Ordering};
-        static COUNTER: AtomicU64 = AtomicU64::new(0);
-        BlockHash(COUNTER.fetch_add(1, Ordering::Relaxed))
+    fn new(ids: &mut IdAllocator) -> Self {
+        BlockHash(ids.next_id())
     }
 }
 
-#[allow(dead_code)]
+#[allow(dead_code, clippy::upper_case_acronyms)]
 /// Transaction output
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 struct TXO {
@@ -32,11 +36,9 @@ struct Note {
 
 #[allow(dead_code)]
 impl Note {
-    fn new(value: u64) -> Self {
-        use std::sync::atomic::{AtomicU64, Ordering};
-        static COUNTER: AtomicU64 = AtomicU64::new(0);
+    fn new(ids: &mut IdAllocator, value: u64) -> Self {
         Note {
-            id: COUNTER.fetch_add(1, Ordering::Relaxed),
+            id: ids.next_id(),
             value,
         }
     }
@@ -57,7 +59,9 @@ struct BCTransaction {
 
 #[allow(dead_code)]
 impl BCTransaction {
+    #[allow(clippy::too_many_arguments)]
     fn new(
+        ids: &mut IdAllocator,
         transparent_inputs: Vec<TXO>,
         transparent_output_values: Vec<u64>,
         shielded_inputs: Vec<Note>,
@@ -66,9 +70,7 @@ impl BCTransaction {
         anchor: Option<BCContext>,
         issuance: u64,
     ) -> Self {
-        use std::sync::atomic::{AtomicU64, Ordering};
-        static COUNTER: AtomicU64 = AtomicU64::new(0);
-        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let id = ids.next_id();
 
         let transparent_outputs = transparent_output_values
             .iter()
@@ -82,7 +84,7 @@ impl BCTransaction {
 
         let shielded_outputs = shielded_output_values
             .iter()
-            .map(|v| Note::new(*v))
+            .map(|v| Note::new(ids, *v))
             .collect();
 
         // Validate transaction
@@ -149,7 +151,7 @@ impl BCContext {
             self.notes
                 .iter()
                 .find(|(n, _)| n == note)
-                .map_or(false, |(_, status)| *status == Spentness::Unspent)
+                .is_some_and(|(_, status)| *status == Spentness::Unspent)
         })
     }
 
@@ -188,8 +190,8 @@ impl BCContext {
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
-struct BCBlock {
+#[derive(Debug, Clone)]
+pub struct BCBlock {
     parent: Option<Box<BCBlock>>,
     score: i64,
     transactions: Vec<BCTransaction>,
@@ -199,6 +201,7 @@ struct BCBlock {
 #[allow(dead_code)]
 impl BCBlock {
     fn new(
+        ids: &mut IdAllocator,
         parent: Option<BCBlock>,
         added_score: i64,
         transactions: Vec<BCTransaction>,
@@ -208,7 +211,7 @@ impl BCBlock {
         let score = parent
             .as_ref()
             .map_or(added_score, |p| p.score + added_score);
-        let hash = BlockHash::new();
+        let hash = BlockHash::new(ids);
 
         let block = BCBlock {
             parent,
@@ -223,6 +226,14 @@ impl BCBlock {
         block
     }
 
+    pub fn hash(&self) -> &BlockHash {
+        &self.hash
+    }
+
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
     fn assert_noncontextually_valid(&self) {
         assert!(!self.transactions.is_empty());
         assert!(self.transactions[0].is_coinbase());
@@ -237,15 +248,32 @@ mod tests {
 
     #[test]
     fn test_basic() {
+        let mut ids = IdAllocator::new();
         let mut ctx = BCContext::new();
 
         // Genesis block
-        let coinbase_tx0 = BCTransaction::new(vec![], vec![10], vec![], vec![], 0, None, 10);
+        let coinbase_tx0 = BCTransaction::new(&mut ids, vec![], vec![10], vec![], vec![], 0, None, 10);
         assert!(ctx.add_if_valid(coinbase_tx0.clone()));
-        let genesis = BCBlock::new(None, 1, vec![coinbase_tx0.clone()], false);
+        let genesis = BCBlock::new(&mut ids, None, 1, vec![coinbase_tx0.clone()], false);
         assert_eq!(genesis.score, 1);
         assert_eq!(ctx.total_issuance, 10);
 
         // More tests can be added following the Python test pattern...
     }
+
+    #[test]
+    fn test_ids_are_per_allocator() {
+        // Two independent simulations allocate the same ID sequences
+        let build = || {
+            let mut ids = IdAllocator::new();
+            let tx = BCTransaction::new(&mut ids, vec![], vec![5], vec![], vec![3, 2], 0, None, 10);
+            let block = BCBlock::new(&mut ids, None, 1, vec![tx.clone()], false);
+            (tx.id, tx.shielded_outputs, block.hash)
+        };
+        let (tx_a, notes_a, hash_a) = build();
+        let (tx_b, notes_b, hash_b) = build();
+        assert_eq!(tx_a, tx_b);
+        assert_eq!(notes_a, notes_b);
+        assert_eq!(hash_a, hash_b);
+    }
 }
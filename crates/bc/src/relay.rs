@@ -0,0 +1,119 @@
+use async_trait::async_trait;
+use std::sync::Mutex;
+
+use message::{Envelope, Message};
+use network::{Flooding, NetworkHandle, Node, Time};
+use utils::{skip, IdAllocator, ProcessEffect};
+
+use crate::{BCBlock, BCTransaction, BlockHash};
+
+/// A node that mines blocks and floods them over the network's topology,
+/// for measuring block propagation. Each node follows the highest-scoring
+/// block it has seen and mines on top of it.
+#[derive(Debug)]
+pub struct BlockRelayNode {
+    ident: i32,
+    network: Option<NetworkHandle>,
+    /// Floods blocks by hash
+    flooding: Flooding<BlockHash>,
+    tip: Mutex<Option<BCBlock>>,
+}
+
+impl BlockRelayNode {
+    pub fn new() -> Self {
+        let hash = |message: &dyn Message| {
+            let block = message.as_any_ref().downcast_ref::<BCBlock>()?;
+            Some(block.hash.clone())
+        };
+        BlockRelayNode {
+            ident: 0,
+            network: None,
+            flooding: Flooding::keyed(4096, hash),
+            tip: Mutex::new(None),
+        }
+    }
+
+    /// Returns the highest-scoring block the node has seen
+    pub fn tip(&self) -> Option<BCBlock> {
+        self.tip.lock().expect("tip poisoned").clone()
+    }
+
+    /// Mines a block holding only a coinbase on top of the node's tip and
+    /// floods it
+    pub async fn mine(&self, ids: &mut IdAllocator) -> BCBlock {
+        let coinbase = BCTransaction::new(ids, vec![], vec![10], vec![], vec![], 0, None, 10);
+        let block = BCBlock::new(ids, self.tip(), 1, vec![coinbase], false);
+        self.follow(&block);
+        self.flooding.publish(self, Box::new(block.clone())).await;
+        block
+    }
+
+    /// Returns the virtual time the node first saw `block`, if it did
+    pub fn arrival(&self, block: &BCBlock) -> Option<Time> {
+        self.flooding.first_seen(block)
+    }
+
+    /// Returns how long `block` took from being mined until every node in
+    /// `nodes` had it. `nodes` must include the miner, whose first sighting
+    /// is the earliest. Returns None if some node never got the block.
+    pub fn propagation_time(nodes: &[&BlockRelayNode], block: &BCBlock) -> Option<Time> {
+        let times = nodes
+            .iter()
+            .map(|node| node.arrival(block))
+            .collect::<Option<Vec<Time>>>()?;
+        Some(times.iter().max()? - times.iter().min()?)
+    }
+
+    fn follow(&self, block: &BCBlock) {
+        let mut tip = self.tip.lock().expect("tip poisoned");
+        if tip.as_ref().is_none_or(|tip| block.score > tip.score) {
+            *tip = Some(block.clone());
+        }
+    }
+}
+
+impl Default for BlockRelayNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Node for BlockRelayNode {
+    fn initialize(&mut self, ident: i32, network: NetworkHandle) {
+        self.ident = ident;
+        self.network = Some(network);
+    }
+
+    fn ident(&self) -> i32 {
+        self.ident
+    }
+
+    fn network(&self) -> &NetworkHandle {
+        self.network.as_ref().expect("Node not initialized")
+    }
+
+    async fn run(&self) -> ProcessEffect {
+        skip().await
+    }
+
+    async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+        let block = envelope
+            .message
+            .as_any_ref()
+            .downcast_ref::<BCBlock>()
+            .cloned();
+        match block {
+            Some(block) => {
+                if self.flooding.relay(self, envelope).await {
+                    self.follow(&block);
+                }
+            }
+            None => {
+                let detail = format!("from {}: {:?}", envelope.sender, envelope.message);
+                self.log("unhandled", &detail).await;
+            }
+        }
+        skip().await
+    }
+}
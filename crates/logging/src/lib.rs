@@ -1,12 +1,13 @@
 use log::{debug, info};
+use std::sync::{Arc, Mutex};
 
 /// A trait for loggers that defines the basic logging interface
 pub trait Logger: Send + Sync {
     /// Print a header (if applicable)
     fn header(&self);
 
-    /// Log an event
-    fn log(&self, ident: i32, event: &str, detail: &str);
+    /// Log an event that happened at virtual time `now`
+    fn log(&self, now: u64, ident: i32, event: &str, detail: &str);
 }
 
 /// A logger that does nothing
@@ -16,12 +17,12 @@ pub struct DebugLogger;
 impl Logger for DebugLogger {
     fn header(&self) {
         // Log the header using log crate
-        info!(" Node | Event      | Detail");
+        info!(" Time | Node | Event      | Detail");
     }
 
-    fn log(&self, ident: i32, event: &str, detail: &str) {
+    fn log(&self, now: u64, ident: i32, event: &str, detail: &str) {
         // Log using log crate
-        debug!(" {:4} | {:10} | {}", ident, event, detail);
+        debug!("{:5} | {:4} | {:10} | {}", now, ident, event, detail);
     }
 }
 
@@ -31,12 +32,46 @@ pub struct PrintLogger;
 impl Logger for PrintLogger {
     fn header(&self) {
         // Log the header using stdout
-        println!(" Node | Event      | Detail");
+        println!(" Time | Node | Event      | Detail");
     }
 
-    fn log(&self, ident: i32, event: &str, detail: &str) {
+    fn log(&self, now: u64, ident: i32, event: &str, detail: &str) {
         // Log using stdout
-        println!("{:4} | {:10} | {}", ident, event, detail);
+        println!("{:5} | {:4} | {:10} | {}", now, ident, event, detail);
+    }
+}
+
+/// A logger that records formatted lines in memory, e.g. to compare runs.
+/// Clones share the same record.
+#[derive(Default, Clone)]
+pub struct RecordingLogger {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingLogger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the lines recorded so far
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().clone()
+    }
+}
+
+impl Logger for RecordingLogger {
+    fn header(&self) {
+        self.lines
+            .lock()
+            .unwrap()
+            .push(" Time | Node | Event      | Detail".to_string());
+    }
+
+    fn log(&self, now: u64, ident: i32, event: &str, detail: &str) {
+        self.lines
+            .lock()
+            .unwrap()
+            .push(format!("{:5} | {:4} | {:10} | {}", now, ident, event, detail));
     }
 }
 
@@ -49,7 +84,7 @@ mod tests {
         // Create a buffer to capture output
         let logger = DebugLogger {};
         logger.header();
-        logger.log(1, "TEST", "test detail");
+        logger.log(0, 1, "TEST", "test detail");
     }
 
     #[test]
@@ -57,6 +92,21 @@ mod tests {
         // Create a buffer to capture output
         let logger = PrintLogger {};
         logger.header();
-        logger.log(1, "TEST", "test detail");
+        logger.log(0, 1, "TEST", "test detail");
+    }
+
+    #[test]
+    fn test_recording_logger() {
+        let logger = RecordingLogger::new();
+        let record = logger.clone();
+        logger.header();
+        logger.log(3, 1, "TEST", "test detail");
+        assert_eq!(
+            record.lines(),
+            vec![
+                " Time | Node | Event      | Detail".to_string(),
+                "    3 |    1 | TEST       | test detail".to_string(),
+            ]
+        );
     }
 }
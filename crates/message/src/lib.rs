@@ -1,9 +1,10 @@
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 
 pub trait Message: std::fmt::Debug + Send + 'static + Any {
     fn box_clone(&self) -> Box<dyn Message>;
     fn as_any_ref(&self) -> &dyn Any;
-
 }
 
 impl<T> Message for T 
@@ -17,7 +18,6 @@ where
     fn as_any_ref(&self) -> &dyn Any {
         self
     }
-
 }
 
 #[derive(Debug, Clone)]
@@ -52,3 +52,209 @@ where
         &self.payload
     }
 }
+
+/// Logical clock of a node: for each node, the number of events of that
+/// node it has seen
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct VectorClock {
+    counts: BTreeMap<i32, u64>,
+}
+
+impl VectorClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of events of `node` this clock has seen
+    pub fn get(&self, node: i32) -> u64 {
+        self.counts.get(&node).copied().unwrap_or(0)
+    }
+
+    /// Counts an event of `node`
+    pub fn increment(&mut self, node: i32) {
+        *self.counts.entry(node).or_insert(0) += 1;
+    }
+
+    /// Takes in everything `other` has seen
+    pub fn merge(&mut self, other: &VectorClock) {
+        for (node, count) in &other.counts {
+            let entry = self.counts.entry(*node).or_insert(0);
+            *entry = (*entry).max(*count);
+        }
+    }
+
+    /// Returns true if everything this clock has seen, `other` has seen too,
+    /// and `other` has seen more
+    pub fn happened_before(&self, other: &VectorClock) -> bool {
+        self != other
+            && self
+                .counts
+                .iter()
+                .all(|(node, count)| *count <= other.get(*node))
+    }
+
+    /// Returns true if neither clock happened before the other
+    pub fn is_concurrent(&self, other: &VectorClock) -> bool {
+        self != other && !self.happened_before(other) && !other.happened_before(self)
+    }
+}
+
+/// A message in flight together with what the network knows about it
+#[derive(Debug)]
+pub struct Envelope {
+    /// Unique per transmission; a duplicated message keeps its id
+    pub id: u64,
+    pub sender: i32,
+    /// Virtual time the message was sent
+    pub sent_at: u64,
+    /// Virtual time the message reached its target
+    pub delivered_at: u64,
+    /// Number of links the message travelled
+    pub hops: u32,
+    /// Vector clock of the sender when it sent the message, if tracked
+    pub clock: Option<VectorClock>,
+    pub message: Box<dyn Message>,
+}
+
+impl Envelope {
+    /// Wraps a message sent directly from `sender` at `sent_at`
+    pub fn new(id: u64, sender: i32, sent_at: u64, message: Box<dyn Message>) -> Self {
+        Envelope {
+            id,
+            sender,
+            sent_at,
+            delivered_at: sent_at,
+            hops: 1,
+            clock: None,
+            message,
+        }
+    }
+
+    /// Returns the message if it is of type `M`
+    pub fn downcast_ref<M: Message>(&self) -> Option<&M> {
+        self.message.as_any_ref().downcast_ref::<M>()
+    }
+}
+
+impl Clone for Envelope {
+    fn clone(&self) -> Self {
+        Envelope {
+            id: self.id,
+            sender: self.sender,
+            sent_at: self.sent_at,
+            delivered_at: self.delivered_at,
+            hops: self.hops,
+            clock: self.clock.clone(),
+            message: self.message.box_clone(),
+        }
+    }
+}
+
+/// A message no handler of a `Router` accepts
+pub struct Unhandled(pub Box<dyn Message>);
+
+impl fmt::Debug for Unhandled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unhandled({:?})", self.0)
+    }
+}
+
+impl fmt::Display for Unhandled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no handler for {:?}", self.0)
+    }
+}
+
+type Handler<R> = Box<dyn Fn(i32, &dyn Message) -> R + Send + Sync>;
+
+/// Dispatches messages to handlers registered per message type, so that
+/// nodes need not chain downcasts. Handlers take the sender and the typed
+/// message and return an `R`, e.g. the reply to send.
+pub struct Router<R> {
+    handlers: HashMap<TypeId, Handler<R>>,
+}
+
+impl<R> Router<R> {
+    pub fn new() -> Self {
+        Router {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers the handler for messages of type `M`, replacing any
+    /// previous one
+    pub fn on<M: Message>(
+        mut self,
+        handler: impl Fn(i32, &M) -> R + Send + Sync + 'static,
+    ) -> Self {
+        let handler = move |sender: i32, message: &dyn Message| {
+            let message = message
+                .as_any_ref()
+                .downcast_ref::<M>()
+                .expect("handler registered for another type");
+            handler(sender, message)
+        };
+        self.handlers.insert(TypeId::of::<M>(), Box::new(handler));
+        self
+    }
+
+    /// Returns true if a handler accepts `message`
+    pub fn handles(&self, message: &dyn Message) -> bool {
+        self.handlers.contains_key(&Any::type_id(message.as_any_ref()))
+    }
+
+    /// Passes `message` to the handler for its type, or returns it as
+    /// unhandled
+    pub fn route(&self, sender: i32, message: Box<dyn Message>) -> Result<R, Unhandled> {
+        match self.handlers.get(&Any::type_id(message.as_any_ref())) {
+            Some(handler) => Ok(handler(sender, message.as_ref())),
+            None => Err(Unhandled(message)),
+        }
+    }
+}
+
+impl<R> Default for Router<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_router() {
+        let router = Router::new()
+            .on::<i32>(|sender, n| format!("{} sent {}", sender, n))
+            .on::<MessageString>(|_, text| text.message.clone());
+
+        assert!(router.handles(&1i32));
+        assert!(!router.handles(&1u64));
+        assert_eq!(router.route(3, Box::new(7i32)).unwrap(), "3 sent 7");
+        let text = MessageString::new("hello".to_string());
+        assert_eq!(router.route(3, Box::new(text)).unwrap(), "hello");
+
+        let unhandled = router.route(3, Box::new(7u64)).unwrap_err();
+        assert_eq!(unhandled.to_string(), "no handler for 7");
+    }
+
+    #[test]
+    fn test_vector_clock() {
+        let mut a = VectorClock::new();
+        a.increment(0);
+        let mut b = a.clone();
+        b.increment(1);
+        assert!(a.happened_before(&b));
+        assert!(!b.happened_before(&a));
+
+        let mut c = a.clone();
+        c.increment(0);
+        assert!(b.is_concurrent(&c));
+        assert!(!a.is_concurrent(&a));
+
+        c.merge(&b);
+        assert_eq!((c.get(0), c.get(1), c.get(2)), (2, 1, 0));
+        assert!(b.happened_before(&c));
+    }
+}
@@ -0,0 +1,229 @@
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+
+use message::Message;
+
+use crate::Time;
+
+/// A message a Byzantine node sends in place of what its honest code sent
+#[derive(Debug)]
+pub struct Outgoing {
+    pub message: Box<dyn Message>,
+    /// Delay on top of the network latency
+    pub delay: Time,
+}
+
+impl Outgoing {
+    /// Sends `message` without extra delay
+    pub fn new(message: Box<dyn Message>) -> Self {
+        Outgoing { message, delay: 0 }
+    }
+}
+
+/// Behavior of a Byzantine node.
+///
+/// The network consults the adversary of a node (see `Node::adversary`) for
+/// every message the node sends, so that honest protocol code can be reused
+/// and perturbed by the adversary.
+pub trait Adversary: Send {
+    /// Decides what is sent when the node's honest code sends `message` to
+    /// `target` at virtual time `now`. Returns the messages to send instead,
+    /// possibly none.
+    fn outgoing(&mut self, now: Time, target: i32, message: Box<dyn Message>) -> Vec<Outgoing>;
+}
+
+/// Several adversaries applied in turn: each one perturbs what the previous
+/// ones sent, and extra delays add up
+impl Adversary for Vec<Box<dyn Adversary>> {
+    fn outgoing(&mut self, now: Time, target: i32, message: Box<dyn Message>) -> Vec<Outgoing> {
+        let mut outgoing = vec![Outgoing::new(message)];
+        for adversary in self.iter_mut() {
+            outgoing = outgoing
+                .into_iter()
+                .flat_map(|sent| {
+                    let mut perturbed = adversary.outgoing(now, target, sent.message);
+                    for next in &mut perturbed {
+                        next.delay += sent.delay;
+                    }
+                    perturbed
+                })
+                .collect();
+        }
+        outgoing
+    }
+}
+
+/// Sends different messages to different peers. The closure returns the
+/// message to send to a target instead of the honest one, or None to send
+/// the honest message.
+pub struct Equivocate<F> {
+    forge: F,
+}
+
+impl<F> Equivocate<F>
+where
+    F: FnMut(i32, &dyn Message) -> Option<Box<dyn Message>> + Send,
+{
+    pub fn new(forge: F) -> Self {
+        Equivocate { forge }
+    }
+}
+
+impl<F> Adversary for Equivocate<F>
+where
+    F: FnMut(i32, &dyn Message) -> Option<Box<dyn Message>> + Send,
+{
+    fn outgoing(&mut self, _now: Time, target: i32, message: Box<dyn Message>) -> Vec<Outgoing> {
+        let message = (self.forge)(target, message.as_ref()).unwrap_or(message);
+        vec![Outgoing::new(message)]
+    }
+}
+
+/// Withholds messages, from everyone or from selected peers
+#[derive(Debug, Clone, Default)]
+pub struct Silence {
+    /// Peers that receive nothing; None silences the node towards everyone
+    targets: Option<HashSet<i32>>,
+}
+
+impl Silence {
+    /// Sends nothing at all
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Sends nothing to `targets`
+    pub fn towards(targets: impl IntoIterator<Item = i32>) -> Self {
+        Silence {
+            targets: Some(targets.into_iter().collect()),
+        }
+    }
+}
+
+impl Adversary for Silence {
+    fn outgoing(&mut self, _now: Time, target: i32, message: Box<dyn Message>) -> Vec<Outgoing> {
+        match &self.targets {
+            Some(targets) if !targets.contains(&target) => vec![Outgoing::new(message)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Holds back messages of selected kinds, e.g. votes
+#[derive(Debug, Clone, Default)]
+pub struct Delay {
+    default: Time,
+    delays: HashMap<TypeId, Time>,
+}
+
+impl Delay {
+    /// Delays every message kind by `default`
+    pub fn new(default: Time) -> Self {
+        Delay {
+            default,
+            delays: HashMap::new(),
+        }
+    }
+
+    /// Delays messages of type `M` by `delay`
+    pub fn with<M: Message>(mut self, delay: Time) -> Self {
+        self.delays.insert(TypeId::of::<M>(), delay);
+        self
+    }
+}
+
+impl Adversary for Delay {
+    fn outgoing(&mut self, _now: Time, _target: i32, message: Box<dyn Message>) -> Vec<Outgoing> {
+        let kind = Any::type_id(message.as_any_ref());
+        let delay = self.delays.get(&kind).copied().unwrap_or(self.default);
+        vec![Outgoing { message, delay }]
+    }
+}
+
+/// Replays old messages: along with each message, sends the message the node
+/// sent `lag` messages earlier again
+#[derive(Debug)]
+pub struct Replay {
+    lag: usize,
+    history: Vec<Box<dyn Message>>,
+}
+
+impl Replay {
+    pub fn new(lag: usize) -> Self {
+        Replay {
+            lag,
+            history: Vec::new(),
+        }
+    }
+}
+
+impl Adversary for Replay {
+    fn outgoing(&mut self, _now: Time, _target: i32, message: Box<dyn Message>) -> Vec<Outgoing> {
+        let old = self
+            .history
+            .len()
+            .checked_sub(self.lag)
+            .and_then(|index| self.history.get(index))
+            .map(|old| old.box_clone());
+        self.history.push(message.box_clone());
+        std::iter::once(message)
+            .chain(old)
+            .map(Outgoing::new)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use message::MessageString;
+
+    fn sent(adversary: &mut dyn Adversary, target: i32, message: i32) -> Vec<(String, Time)> {
+        adversary
+            .outgoing(0, target, Box::new(message))
+            .into_iter()
+            .map(|outgoing| (format!("{:?}", outgoing.message), outgoing.delay))
+            .collect()
+    }
+
+    #[test]
+    fn test_strategies() {
+        let mut equivocate = Equivocate::new(|target, _message: &dyn Message| {
+            (target == 2).then(|| Box::new(-1) as Box<dyn Message>)
+        });
+        assert_eq!(sent(&mut equivocate, 1, 7), vec![("7".to_string(), 0)]);
+        assert_eq!(sent(&mut equivocate, 2, 7), vec![("-1".to_string(), 0)]);
+
+        let mut silence = Silence::towards([1]);
+        assert!(sent(&mut silence, 1, 7).is_empty());
+        assert_eq!(sent(&mut silence, 2, 7).len(), 1);
+        assert!(sent(&mut Silence::all(), 2, 7).is_empty());
+
+        let mut delay = Delay::new(0).with::<i32>(10);
+        assert_eq!(sent(&mut delay, 1, 7), vec![("7".to_string(), 10)]);
+        let text = delay.outgoing(0, 1, Box::new(MessageString::new("x".to_string())));
+        assert_eq!(text[0].delay, 0);
+
+        let mut replay = Replay::new(2);
+        assert_eq!(sent(&mut replay, 1, 1).len(), 1);
+        assert_eq!(sent(&mut replay, 1, 2).len(), 1);
+        assert_eq!(
+            sent(&mut replay, 1, 3),
+            vec![("3".to_string(), 0), ("1".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn test_adversaries_compose() {
+        let mut adversaries: Vec<Box<dyn Adversary>> = vec![
+            Box::new(Replay::new(1)),
+            Box::new(Delay::new(5)),
+            Box::new(Delay::new(1)),
+        ];
+        sent(&mut adversaries, 1, 1);
+        assert_eq!(
+            sent(&mut adversaries, 1, 2),
+            vec![("2".to_string(), 6), ("1".to_string(), 6)]
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+use message::Message;
+
+use crate::Time;
+
+/// Bandwidth of the links between nodes, in bytes per unit of virtual time:
+/// a default for every link plus per-link overrides. Links without a
+/// bandwidth transfer messages instantly.
+///
+/// Each link sends one message at a time, so a message waits until the
+/// messages sent before it on the same link have been transferred.
+#[derive(Debug, Clone, Default)]
+pub struct Bandwidth {
+    default: Option<u64>,
+    links: HashMap<(i32, i32), u64>,
+}
+
+impl Bandwidth {
+    /// Every link transfers `default` bytes per unit of virtual time
+    pub fn new(default: u64) -> Self {
+        Bandwidth {
+            default: Some(default),
+            links: HashMap::new(),
+        }
+    }
+
+    /// Only the links set with `set_link` are limited
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Sets the bandwidth of the link from sender to target
+    pub fn set_link(&mut self, sender: i32, target: i32, bytes_per_time: u64) {
+        self.links.insert((sender, target), bytes_per_time);
+    }
+
+    /// Returns the bandwidth of the link from sender to target, if limited
+    pub fn link(&self, sender: i32, target: i32) -> Option<u64> {
+        self.links.get(&(sender, target)).copied().or(self.default)
+    }
+
+    /// Returns true if no link is limited
+    pub fn is_unlimited(&self) -> bool {
+        self.default.is_none() && self.links.is_empty()
+    }
+
+    /// Returns the virtual time the link from sender to target takes to
+    /// transfer `size` bytes, rounded up
+    pub fn transfer_time(&self, sender: i32, target: i32, size: usize) -> Time {
+        match self.link(sender, target) {
+            Some(rate) => (size as u64).div_ceil(rate.max(1)),
+            None => 0,
+        }
+    }
+}
+
+type SizeFn = Box<dyn Fn(&dyn Message) -> usize + Send + Sync>;
+
+/// Sizes of message types in bytes. Messages of other types are sized by
+/// the length of their debug form, which grows with their content.
+#[derive(Default)]
+pub struct MessageSizes {
+    sizes: HashMap<TypeId, SizeFn>,
+}
+
+impl MessageSizes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sizes messages of type `M` with `size`
+    pub fn with<M: Message>(mut self, size: impl Fn(&M) -> usize + Send + Sync + 'static) -> Self {
+        let size: SizeFn = Box::new(move |message: &dyn Message| {
+            let message = message.as_any_ref().downcast_ref::<M>();
+            size(message.expect("sized by type id"))
+        });
+        self.sizes.insert(TypeId::of::<M>(), size);
+        self
+    }
+
+    /// Returns the size of `message` in bytes
+    pub fn size(&self, message: &dyn Message) -> usize {
+        let kind = Any::type_id(message.as_any_ref());
+        match self.sizes.get(&kind) {
+            Some(size) => size(message),
+            None => Self::estimate(message),
+        }
+    }
+
+    /// Estimates the size of a message of a type without an exact size
+    pub fn estimate(message: &dyn Message) -> usize {
+        format!("{:?}", message).len()
+    }
+}
+
+impl fmt::Debug for MessageSizes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MessageSizes({} overrides)", self.sizes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use message::MessageString;
+
+    #[test]
+    fn test_transfer_time() {
+        let mut bandwidth = Bandwidth::new(10);
+        bandwidth.set_link(0, 1, 100);
+        assert_eq!(bandwidth.transfer_time(1, 0, 25), 3);
+        assert_eq!(bandwidth.transfer_time(0, 1, 25), 1);
+        assert_eq!(bandwidth.transfer_time(0, 1, 0), 0);
+        assert_eq!(Bandwidth::unlimited().transfer_time(0, 1, 1000), 0);
+    }
+
+    #[test]
+    fn test_message_sizes() {
+        let sizes = MessageSizes::new().with::<MessageString>(|text| 40 + text.message.len());
+        assert_eq!(sizes.size(&MessageString::new("block".to_string())), 45);
+        // Other messages are estimated from their debug form
+        assert_eq!(sizes.size(&12345i32), 5);
+        assert!(Bandwidth::unlimited().is_unlimited());
+        assert!(!Bandwidth::new(10).is_unlimited());
+    }
+}
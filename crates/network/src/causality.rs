@@ -0,0 +1,193 @@
+use std::fmt;
+
+use message::VectorClock;
+
+use crate::Time;
+
+/// Whether a node sent or received a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CausalKind {
+    Send,
+    Receive,
+}
+
+/// A send or receive, stamped with the vector clock of its node just after
+/// the event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CausalEvent {
+    pub time: Time,
+    /// Node the event happened at
+    pub node: i32,
+    pub kind: CausalKind,
+    /// Envelope id of the message
+    pub message_id: u64,
+    /// Target of a send, sender of a receive
+    pub peer: i32,
+    pub clock: VectorClock,
+}
+
+impl fmt::Display for CausalEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (verb, direction) = match self.kind {
+            CausalKind::Send => ("sends", "to"),
+            CausalKind::Receive => ("receives", "from"),
+        };
+        write!(
+            f,
+            "{} {} message {} {} {} at {}",
+            self.node, verb, self.message_id, direction, self.peer, self.time
+        )
+    }
+}
+
+/// The sends and receives of a run, for answering happens-before questions
+/// after the run. Recorded by the network, see `Network::record_causality`.
+///
+/// Events are identified by their index in `events`.
+#[derive(Debug, Clone, Default)]
+pub struct Causality {
+    events: Vec<CausalEvent>,
+}
+
+impl Causality {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, event: CausalEvent) {
+        self.events.push(event);
+    }
+
+    pub fn events(&self) -> &[CausalEvent] {
+        &self.events
+    }
+
+    /// Returns true if event `a` happened before event `b`, i.e. there is a
+    /// chain of events at nodes and messages between them leading from `a`
+    /// to `b`
+    pub fn happened_before(&self, a: usize, b: usize) -> bool {
+        self.events[a].clock.happened_before(&self.events[b].clock)
+    }
+
+    /// Returns true if neither event happened before the other
+    pub fn concurrent(&self, a: usize, b: usize) -> bool {
+        self.events[a].clock.is_concurrent(&self.events[b].clock)
+    }
+
+    /// Returns the index of the event that sent message `id`
+    pub fn send_of(&self, id: u64) -> Option<usize> {
+        self.position(CausalKind::Send, id)
+    }
+
+    /// Returns the index of the first event that received message `id`
+    pub fn receive_of(&self, id: u64) -> Option<usize> {
+        self.position(CausalKind::Receive, id)
+    }
+
+    /// Returns the ids of every pair of messages whose sends are concurrent,
+    /// lowest ids first
+    pub fn concurrent_messages(&self) -> Vec<(u64, u64)> {
+        let sends: Vec<&CausalEvent> = self
+            .events
+            .iter()
+            .filter(|event| event.kind == CausalKind::Send)
+            .collect();
+        let mut pairs = Vec::new();
+        for (i, a) in sends.iter().enumerate() {
+            for b in &sends[i + 1..] {
+                if a.clock.is_concurrent(&b.clock) {
+                    pairs.push((
+                        a.message_id.min(b.message_id),
+                        a.message_id.max(b.message_id),
+                    ));
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    /// Returns the pairs of messages `(a, b)` received by the same node in
+    /// the opposite order of their causal order: the send of `a` happened
+    /// before the send of `b`, but `b` was received first
+    pub fn causal_order_violations(&self) -> Vec<(u64, u64)> {
+        let receives: Vec<(usize, &CausalEvent)> = self
+            .events
+            .iter()
+            .enumerate()
+            .filter(|(_, event)| event.kind == CausalKind::Receive)
+            .collect();
+        let mut violations = Vec::new();
+        for (i, (_, first)) in receives.iter().enumerate() {
+            for (_, second) in &receives[i + 1..] {
+                if first.node != second.node {
+                    continue;
+                }
+                let sends = (
+                    self.send_of(first.message_id),
+                    self.send_of(second.message_id),
+                );
+                if let (Some(a), Some(b)) = sends {
+                    if self.happened_before(b, a) {
+                        violations.push((second.message_id, first.message_id));
+                    }
+                }
+            }
+        }
+        violations
+    }
+
+    fn position(&self, kind: CausalKind, id: u64) -> Option<usize> {
+        self.events
+            .iter()
+            .position(|event| event.kind == kind && event.message_id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(node: i32, kind: CausalKind, message_id: u64, clock: &[(i32, u64)]) -> CausalEvent {
+        let mut stamp = VectorClock::new();
+        for (ident, count) in clock {
+            for _ in 0..*count {
+                stamp.increment(*ident);
+            }
+        }
+        CausalEvent {
+            time: 0,
+            node,
+            kind,
+            message_id,
+            peer: -1,
+            clock: stamp,
+        }
+    }
+
+    #[test]
+    fn test_happens_before() {
+        let mut causality = Causality::new();
+        // 0 sends m0 to 2 and then m1 to 1, which forwards it as m2 to 2;
+        // 2 receives m2 before m0
+        causality.push(event(0, CausalKind::Send, 0, &[(0, 1)]));
+        causality.push(event(0, CausalKind::Send, 1, &[(0, 2)]));
+        causality.push(event(1, CausalKind::Receive, 1, &[(0, 2), (1, 1)]));
+        causality.push(event(1, CausalKind::Send, 2, &[(0, 2), (1, 2)]));
+        causality.push(event(2, CausalKind::Receive, 2, &[(0, 2), (1, 2), (2, 1)]));
+        causality.push(event(2, CausalKind::Receive, 0, &[(0, 2), (1, 2), (2, 2)]));
+        // A send by 3, which never heard from anyone
+        causality.push(event(3, CausalKind::Send, 3, &[(3, 1)]));
+
+        assert!(causality.happened_before(0, 4));
+        assert!(!causality.happened_before(4, 0));
+        assert!(causality.concurrent(2, 6));
+        assert_eq!(causality.send_of(2), Some(3));
+        assert_eq!(causality.receive_of(0), Some(5));
+        assert_eq!(
+            causality.concurrent_messages(),
+            vec![(0, 3), (1, 3), (2, 3)]
+        );
+        assert_eq!(causality.causal_order_violations(), vec![(0, 2)]);
+    }
+}
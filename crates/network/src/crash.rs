@@ -0,0 +1,14 @@
+use crate::Node;
+
+/// How a crashed node comes back
+#[derive(Debug)]
+pub enum Restart {
+    /// The same node restarts with whatever state it persisted, through
+    /// `Node::recover`
+    Persisted,
+    /// A fresh node takes the crashed node's place and is started with
+    /// `Node::run`. The network owns it until then, so that it can initialize
+    /// it with the crashed node's ident; fetch it with `Network::node` to
+    /// inspect it afterwards.
+    Fresh(Box<dyn Node>),
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use crate::{Candidate, Network, Simulation, Trace};
+
+/// An interleaving that violates the invariant
+#[derive(Debug, Clone)]
+pub struct Violation {
+    /// What the invariant reported
+    pub message: String,
+    /// Number of events processed before the violation
+    pub depth: usize,
+    /// Trace of the violating run; replay it with `Network::replay` on a
+    /// freshly built network to reproduce the violation
+    pub trace: Trace,
+}
+
+/// Outcome of an exploration
+#[derive(Debug, Clone, Default)]
+pub struct ExplorationReport {
+    /// Interleavings followed to quiescence or to the depth bound
+    pub executions: u64,
+    /// States whose successors were explored
+    pub states: u64,
+    /// States skipped because they had been explored already
+    pub pruned: u64,
+    /// The first violation found, if any
+    pub violation: Option<Violation>,
+}
+
+/// A pending step of the depth-first search: the events to process, in
+/// order, to reach a state, and the events not to try from it because an
+/// equivalent interleaving has been explored already
+struct Branch {
+    path: Vec<u64>,
+    sleep: Vec<Candidate>,
+}
+
+/// Bounded model checker that enumerates the interleavings of a small network.
+///
+/// The network is treated as asynchronous: at each step any pending event may
+/// be processed next, regardless of virtual time. Each state is reached by
+/// rebuilding the network and replaying the events leading to it, so `build`
+/// must construct the same network every time. Nodes are started by `build`
+/// or by the first events it schedules.
+///
+/// Invariants registered on the network with `Network::add_invariant` are
+/// checked along with the explorer's own.
+///
+/// Two reductions keep the search small:
+/// - states the network hashes equal (see `Network::state_hash`) are only
+///   explored once;
+/// - events at different nodes are independent, so only one order of each
+///   pair of them is explored (sleep sets).
+///
+/// Both assume that the order events are processed in does not change what
+/// the network draws from its RNG. When latencies or faults are random (see
+/// `Network::is_random`) neither holds, and every interleaving is explored.
+pub struct Explorer<B, I> {
+    build: B,
+    invariant: I,
+    max_depth: Option<usize>,
+}
+
+impl<B, F, I> Explorer<B, I>
+where
+    B: FnMut() -> F,
+    F: Future<Output = Simulation>,
+    I: FnMut(&Network) -> Result<(), String>,
+{
+    /// Creates an explorer of the networks built by `build` that checks
+    /// `invariant` in every reachable state
+    pub fn new(build: B, invariant: I) -> Self {
+        Explorer {
+            build,
+            invariant,
+            max_depth: None,
+        }
+    }
+
+    /// Stops following an interleaving after `max_depth` events
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Explores interleavings depth-first until all have been explored or
+    /// the invariant is violated
+    pub async fn explore(&mut self) -> ExplorationReport {
+        let mut report = ExplorationReport::default();
+        // For each state, the depths and sleep sets it was explored with
+        let mut visited: HashMap<u64, Vec<(usize, Vec<String>)>> = HashMap::new();
+        let mut stack = vec![Branch {
+            path: Vec::new(),
+            sleep: Vec::new(),
+        }];
+
+        while let Some(Branch { path, sleep }) = stack.pop() {
+            let simulation = self.replay(&path, false).await;
+            let network = simulation.network();
+            let network = network.lock().await;
+
+            // Invariants registered on the network count as well
+            let checked = match network.violation() {
+                Some(violation) => Err(violation.to_string()),
+                None => (self.invariant)(&network),
+            };
+            if let Err(message) = checked {
+                drop(network);
+                let trace = self
+                    .replay(&path, true)
+                    .await
+                    .network()
+                    .lock()
+                    .await
+                    .take_trace();
+                report.violation = Some(Violation {
+                    message,
+                    depth: path.len(),
+                    trace: trace.unwrap_or_default(),
+                });
+                return report;
+            }
+
+            let enabled = network.pending_candidates();
+            if enabled.is_empty() || self.max_depth.is_some_and(|max| path.len() >= max) {
+                report.executions += 1;
+                continue;
+            }
+
+            let reduce = !network.is_random();
+            if let Some(hash) = network.state_hash().filter(|_| reduce) {
+                let mut asleep: Vec<String> = sleep.iter().map(signature).collect();
+                asleep.sort();
+                let seen = visited.entry(hash).or_default();
+                // Everything reachable from here was explored before, with at
+                // least as much depth left and no more events asleep
+                if seen
+                    .iter()
+                    .any(|(depth, before)| *depth <= path.len() && is_subset(before, &asleep))
+                {
+                    report.pruned += 1;
+                    continue;
+                }
+                seen.push((path.len(), asleep));
+            }
+            report.states += 1;
+
+            let mut sleep = sleep;
+            let mut branches = Vec::new();
+            for event in enabled {
+                if sleep.iter().any(|asleep| asleep.seq == event.seq) {
+                    continue;
+                }
+                let mut branch_path = path.clone();
+                branch_path.push(event.seq);
+                branches.push(Branch {
+                    path: branch_path,
+                    sleep: sleep
+                        .iter()
+                        .filter(|asleep| reduce && independent(asleep, &event))
+                        .cloned()
+                        .collect(),
+                });
+                sleep.push(event);
+            }
+            stack.extend(branches.into_iter().rev());
+        }
+        report
+    }
+
+    /// Builds a fresh network and processes the events of `path` in order
+    async fn replay(&mut self, path: &[u64], record: bool) -> Simulation {
+        let simulation = (self.build)().await;
+        let network = simulation.network();
+        if record {
+            network.lock().await.record_trace();
+        }
+        for seq in path {
+            Network::step_event(&network, *seq).await;
+        }
+        simulation
+    }
+}
+
+/// Events at different nodes commute; network events commute with nothing
+fn independent(a: &Candidate, b: &Candidate) -> bool {
+    a.target >= 0 && b.target >= 0 && a.target != b.target
+}
+
+fn signature(candidate: &Candidate) -> String {
+    format!(
+        "{} {} {}",
+        candidate.sender, candidate.target, candidate.description
+    )
+}
+
+/// Returns true if every element of the sorted `a` is in the sorted `b`
+fn is_subset(a: &[String], b: &[String]) -> bool {
+    a.iter().all(|item| b.binary_search(item).is_ok())
+}
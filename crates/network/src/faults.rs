@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use crate::Time;
+
+/// Faults injected into the messages sent over a link
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkFaults {
+    /// Probability that a message is dropped
+    pub drop_probability: f64,
+    /// Probability that a message is delivered twice
+    pub duplicate_probability: f64,
+    /// Maximum extra delay added to a message, so that messages sent on the
+    /// link within this bound of each other may arrive out of order
+    pub reorder_bound: Time,
+}
+
+impl LinkFaults {
+    /// Returns true if no faults are injected
+    pub fn is_reliable(&self) -> bool {
+        *self == LinkFaults::default()
+    }
+}
+
+/// Fault configuration of the network: a default for every link plus
+/// per-link overrides
+#[derive(Debug, Clone, Default)]
+pub struct FaultConfig {
+    default: LinkFaults,
+    links: HashMap<(i32, i32), LinkFaults>,
+}
+
+impl FaultConfig {
+    /// Creates a configuration where every link has the `default` faults
+    pub fn new(default: LinkFaults) -> Self {
+        FaultConfig {
+            default,
+            links: HashMap::new(),
+        }
+    }
+
+    /// Sets the faults of the link from sender to target
+    pub fn set_link(&mut self, sender: i32, target: i32, faults: LinkFaults) {
+        self.links.insert((sender, target), faults);
+    }
+
+    /// Returns the faults of the link from sender to target
+    pub fn link(&self, sender: i32, target: i32) -> LinkFaults {
+        self.links
+            .get(&(sender, target))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Returns true if no faults are injected on any link
+    pub fn is_reliable(&self) -> bool {
+        self.default.is_reliable() && self.links.values().all(LinkFaults::is_reliable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_overrides() {
+        let lossy = LinkFaults {
+            drop_probability: 0.5,
+            ..LinkFaults::default()
+        };
+        let mut faults = FaultConfig::default();
+        faults.set_link(0, 1, lossy);
+
+        assert_eq!(faults.link(0, 1), lossy);
+        assert!(faults.link(1, 0).is_reliable());
+        assert!(!faults.is_reliable());
+        assert!(FaultConfig::default().is_reliable());
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use message::{Envelope, Message};
+use utils::{skip, ProcessEffect};
+
+use crate::{Node, Time};
+
+/// Floods messages over the topology, for nodes embedding it: a message is
+/// sent to every neighbor, and each node relays it to its own neighbors the
+/// first time it sees it.
+///
+/// Messages are told apart by a key. By default the key is the message's
+/// debug form, so two messages that print the same are only flooded once;
+/// protocols whose messages carry an identifier key on it with `keyed`.
+/// Only the last `capacity` keys are remembered; older messages would be
+/// flooded again if they came back.
+pub struct Flooding<K = String> {
+    capacity: usize,
+    key: KeyFn<K>,
+    seen: Mutex<Seen<K>>,
+}
+
+type KeyFn<K> = Box<dyn Fn(&dyn Message) -> Option<K> + Send + Sync>;
+
+/// Virtual time each remembered message was first seen, by key
+struct Seen<K> {
+    times: HashMap<K, Time>,
+    /// Remembered keys, oldest first
+    order: VecDeque<K>,
+}
+
+impl Default for Flooding {
+    fn default() -> Self {
+        Self::with_capacity(4096)
+    }
+}
+
+impl Flooding {
+    /// Keys messages on their debug form and remembers the last 4096
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keys messages on their debug form and remembers the last `capacity`
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::keyed(capacity, |message| Some(format!("{:?}", message)))
+    }
+}
+
+impl<K: Hash + Eq + Clone> Flooding<K> {
+    /// Keys messages with `key` and remembers the last `capacity` keys.
+    /// Messages `key` returns None for are not flooded.
+    pub fn keyed<F>(capacity: usize, key: F) -> Self
+    where
+        F: Fn(&dyn Message) -> Option<K> + Send + Sync + 'static,
+    {
+        Flooding {
+            capacity,
+            key: Box::new(key),
+            seen: Mutex::new(Seen {
+                times: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Records that the message was seen at `time`; returns false if it had
+    /// been seen before or has no key
+    fn mark(&self, message: &dyn Message, time: Time) -> bool {
+        let Some(key) = (self.key)(message) else {
+            return false;
+        };
+        let mut seen = self.seen.lock().expect("flooding poisoned");
+        if seen.times.contains_key(&key) {
+            return false;
+        }
+        if seen.order.len() >= self.capacity {
+            if let Some(oldest) = seen.order.pop_front() {
+                seen.times.remove(&oldest);
+            }
+        }
+        seen.order.push_back(key.clone());
+        seen.times.insert(key, time);
+        true
+    }
+
+    /// Starts flooding `message` from `node`
+    pub async fn publish<N: Node + ?Sized>(
+        &self,
+        node: &N,
+        message: Box<dyn Message>,
+    ) -> ProcessEffect {
+        let network = node.network();
+        if self.mark(message.as_ref(), network.now()) {
+            network.broadcast(node.ident(), message, None);
+        }
+        skip().await
+    }
+
+    /// Relays a message `node` received to all its neighbors but the sender,
+    /// unless it has seen the message before. Returns true if the message
+    /// was new.
+    pub async fn relay<N: Node + ?Sized>(&self, node: &N, envelope: Envelope) -> bool {
+        if !self.mark(envelope.message.as_ref(), envelope.delivered_at) {
+            return false;
+        }
+        let network = node.network();
+        for target in network.neighbors(node.ident()) {
+            if target != envelope.sender {
+                network.relay(node.ident(), target, envelope.clone(), None);
+            }
+        }
+        true
+    }
+
+    /// Returns the virtual time the message was first seen, if it was and
+    /// is still remembered
+    pub fn first_seen(&self, message: &dyn Message) -> Option<Time> {
+        let key = (self.key)(message)?;
+        let seen = self.seen.lock().expect("flooding poisoned");
+        seen.times.get(&key).copied()
+    }
+}
+
+impl<K> fmt::Debug for Flooding<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Flooding(capacity {})", self.capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use message::MessageString;
+
+    #[test]
+    fn test_forgets_oldest_messages() {
+        let flooding = Flooding::with_capacity(2);
+        let message = |text: &str| MessageString::new(text.to_string());
+        assert!(flooding.mark(&message("a"), 1));
+        assert!(!flooding.mark(&message("a"), 2));
+        assert!(flooding.mark(&message("b"), 3));
+        assert!(flooding.mark(&message("c"), 4));
+        assert_eq!(flooding.first_seen(&message("a")), None);
+        assert_eq!(flooding.first_seen(&message("b")), Some(3));
+        // A forgotten message is new again
+        assert!(flooding.mark(&message("a"), 5));
+    }
+
+    #[test]
+    fn test_keyed() {
+        // Messages with the same key are the same message, whatever they
+        // print; messages without a key are never flooded
+        let flooding = Flooding::keyed(2, |message| {
+            let text = &message
+                .as_any_ref()
+                .downcast_ref::<MessageString>()?
+                .message;
+            text.split(':').next().map(str::to_string)
+        });
+        let message = |text: &str| MessageString::new(text.to_string());
+        assert!(flooding.mark(&message("a:1"), 1));
+        assert!(!flooding.mark(&message("a:2"), 2));
+        assert_eq!(flooding.first_seen(&message("a:3")), Some(1));
+        assert!(!flooding.mark(&42u32, 3));
+    }
+}
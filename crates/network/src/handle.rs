@@ -0,0 +1,218 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use message::{Envelope, Message};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use utils::SimRng;
+
+use crate::{Time, TimerId, Topology};
+
+/// A request a node makes through its handle, applied by the network in
+/// the order it was made
+#[derive(Debug)]
+pub(crate) enum Command {
+    Log {
+        ident: i32,
+        event: String,
+        detail: String,
+    },
+    Send {
+        sender: i32,
+        target: i32,
+        message: Box<dyn Message>,
+        delay: Option<u32>,
+    },
+    Broadcast {
+        sender: i32,
+        message: Box<dyn Message>,
+        delay: Option<u32>,
+    },
+    Relay {
+        sender: i32,
+        target: i32,
+        envelope: Envelope,
+        delay: Option<u32>,
+    },
+    SetTimer {
+        ident: i32,
+        after: Time,
+        tag: u64,
+        timer: TimerId,
+    },
+    CancelTimer(TimerId),
+}
+
+/// What the network publishes for its handles to read without locking it
+#[derive(Debug)]
+struct Shared {
+    seed: u64,
+    now: AtomicU64,
+    num_nodes: AtomicUsize,
+    next_timer: AtomicU64,
+    /// Only written when the topology is replaced
+    topology: RwLock<Option<Arc<Topology>>>,
+}
+
+/// A node's handle on the network.
+///
+/// Sends, logs and timers made through the handle are queued on a channel
+/// into the network instead of taking the network lock; the network applies
+/// them in order once the handler that made them returns, before the next
+/// event is processed, so runs stay deterministic. Cloning a handle is cheap.
+#[derive(Clone)]
+pub struct NetworkHandle {
+    commands: UnboundedSender<Command>,
+    shared: Arc<Shared>,
+}
+
+impl NetworkHandle {
+    /// Creates a handle for a network seeded with `seed`, and the receiving
+    /// end of its commands
+    pub(crate) fn new(seed: u64) -> (Self, UnboundedReceiver<Command>) {
+        let (commands, receiver) = unbounded_channel();
+        let shared = Arc::new(Shared {
+            seed,
+            now: AtomicU64::new(0),
+            num_nodes: AtomicUsize::new(0),
+            next_timer: AtomicU64::new(0),
+            topology: RwLock::new(None),
+        });
+        (NetworkHandle { commands, shared }, receiver)
+    }
+
+    fn command(&self, command: Command) {
+        // The network owns the receiver, so it outlives every handle
+        self.commands
+            .send(command)
+            .expect("network dropped while its nodes are running");
+    }
+
+    /// Returns the current virtual time
+    pub fn now(&self) -> Time {
+        self.shared.now.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of nodes
+    pub fn num_nodes(&self) -> usize {
+        self.shared.num_nodes.load(Ordering::Acquire)
+    }
+
+    /// Returns the nodes `ident` can send to, in ident order
+    pub fn neighbors(&self, ident: i32) -> Vec<i32> {
+        let topology = self.shared.topology.read().expect("topology poisoned");
+        match topology.as_ref() {
+            Some(topology) => topology.neighbors(ident),
+            None => (0..self.num_nodes() as i32)
+                .filter(|target| *target != ident)
+                .collect(),
+        }
+    }
+
+    /// Returns an RNG for node `ident`, derived from the simulation seed.
+    /// Nodes that need randomness of their own draw it from this stream,
+    /// which is independent of the network's and of other nodes'.
+    pub fn rng(&self, ident: i32) -> SimRng {
+        SimRng::stream(self.shared.seed, ident as u32 as u64)
+    }
+
+    /// Logs an event for a node
+    pub fn log(&self, ident: i32, event: &str, detail: &str) {
+        self.command(Command::Log {
+            ident,
+            event: event.to_string(),
+            detail: detail.to_string(),
+        });
+    }
+
+    /// Sends a message from one node to another
+    pub fn send(&self, sender: i32, target: i32, message: Box<dyn Message>, delay: Option<u32>) {
+        self.command(Command::Send {
+            sender,
+            target,
+            message,
+            delay,
+        });
+    }
+
+    /// Broadcasts a message to all neighbors of the sender
+    pub fn broadcast(&self, sender: i32, message: Box<dyn Message>, delay: Option<u32>) {
+        self.command(Command::Broadcast {
+            sender,
+            message,
+            delay,
+        });
+    }
+
+    /// Passes on a message received in `envelope` to `target`, one hop
+    /// further than it came
+    pub fn relay(&self, sender: i32, target: i32, envelope: Envelope, delay: Option<u32>) {
+        self.command(Command::Relay {
+            sender,
+            target,
+            envelope,
+            delay,
+        });
+    }
+
+    /// Sets a timer that fires `on_timer(tag)` on node `ident` after `after`
+    /// units of virtual time
+    pub fn set_timer(&self, ident: i32, after: Time, tag: u64) -> TimerId {
+        let timer = self.next_timer();
+        self.command(Command::SetTimer {
+            ident,
+            after,
+            tag,
+            timer,
+        });
+        timer
+    }
+
+    /// Cancels a timer; cancelling a timer that already fired has no effect
+    pub fn cancel_timer(&self, timer: TimerId) {
+        self.command(Command::CancelTimer(timer));
+    }
+
+    pub(crate) fn next_timer(&self) -> TimerId {
+        TimerId(self.shared.next_timer.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub(crate) fn set_now(&self, now: Time) {
+        self.shared.now.store(now, Ordering::Release);
+    }
+
+    pub(crate) fn set_num_nodes(&self, num_nodes: usize) {
+        self.shared.num_nodes.store(num_nodes, Ordering::Release);
+    }
+
+    pub(crate) fn set_topology(&self, topology: Option<Arc<Topology>>) {
+        *self.shared.topology.write().expect("topology poisoned") = topology;
+    }
+}
+
+impl fmt::Debug for NetworkHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NetworkHandle(seed {})", self.shared.seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::latency::Constant;
+    use crate::Network;
+    use logging::DebugLogger;
+
+    #[test]
+    fn test_node_streams_are_independent() {
+        let network = Network::new(7, None, Box::new(Constant(1)), Box::new(DebugLogger));
+        let mut network = network.try_lock().unwrap();
+        let handle = network.handle();
+        let draws = |rng: &mut utils::SimRng| (0..4).map(|_| rng.next_u64()).collect::<Vec<_>>();
+        let shared = draws(network.rng());
+        let first = draws(&mut handle.rng(0));
+        assert_ne!(first, shared);
+        assert_ne!(first, draws(&mut handle.rng(1)));
+        // Each node's stream is reproducible
+        assert_eq!(first, draws(&mut handle.rng(0)));
+    }
+}
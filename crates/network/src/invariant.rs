@@ -0,0 +1,42 @@
+use std::fmt;
+
+use crate::{Candidate, Network, Trace};
+
+/// A predicate over the network and its nodes. It returns a description of
+/// what is wrong when it does not hold.
+pub type InvariantCheck = Box<dyn Fn(&Network) -> Result<(), String> + Send + Sync>;
+
+/// A named invariant registered with `Network::add_invariant`
+pub(crate) struct Invariant {
+    pub(crate) name: String,
+    pub(crate) check: InvariantCheck,
+}
+
+/// An invariant that failed after an event was processed
+#[derive(Debug, Clone)]
+pub struct InvariantViolation {
+    /// Name the invariant was registered under
+    pub invariant: String,
+    /// What the invariant reported
+    pub message: String,
+    /// The event after which the invariant failed
+    pub event: Candidate,
+    /// Trace of the run up to and including that event
+    pub trace: Trace,
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invariant {:?} violated at time {} after event {} ({} -> {}: {}): {}",
+            self.invariant,
+            self.event.time,
+            self.event.seq,
+            self.event.sender,
+            self.event.target,
+            self.event.description,
+            self.message
+        )
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use utils::SimRng;
+
+use crate::Time;
+
+/// Decides how long a message takes to travel from sender to target.
+///
+/// Random models draw from the simulation RNG they are given, so latencies
+/// are reproducible under the simulation seed.
+pub trait LatencyModel: Send + Sync {
+    /// Returns the latency of a message sent now from sender to target
+    fn latency(&mut self, sender: i32, target: i32, rng: &mut SimRng) -> Time;
+
+    /// Returns true if the model may draw from the RNG. Models that never do
+    /// say so, which lets the explorer reorder sends without changing their
+    /// latencies.
+    fn is_random(&self) -> bool {
+        true
+    }
+}
+
+/// Converts a sampled latency to virtual time, rounding to the nearest unit.
+/// Negative samples become 0, and samples beyond `Time::MAX`, which
+/// heavy-tailed distributions can draw, saturate to it: such a message is
+/// scheduled at the end of time and in practice never delivered.
+fn to_time(sample: f64) -> Time {
+    sample.max(0.0).round() as Time
+}
+
+/// The same latency on every link
+#[derive(Debug, Clone, Copy)]
+pub struct Constant(pub Time);
+
+impl LatencyModel for Constant {
+    fn latency(&mut self, _sender: i32, _target: i32, _rng: &mut SimRng) -> Time {
+        self.0
+    }
+
+    fn is_random(&self) -> bool {
+        false
+    }
+}
+
+/// Latency uniformly distributed between `min` and `max`, inclusive
+#[derive(Debug, Clone, Copy)]
+pub struct Uniform {
+    min: Time,
+    max: Time,
+}
+
+impl Uniform {
+    pub fn new(min: Time, max: Time) -> Self {
+        assert!(min <= max, "uniform latency range {}..={} is empty", min, max);
+        Uniform { min, max }
+    }
+}
+
+impl LatencyModel for Uniform {
+    fn latency(&mut self, _sender: i32, _target: i32, rng: &mut SimRng) -> Time {
+        rng.gen_range_inclusive(self.min..=self.max)
+    }
+}
+
+/// Exponentially distributed latency with the given mean
+#[derive(Debug, Clone, Copy)]
+pub struct Exponential {
+    pub mean: f64,
+}
+
+impl LatencyModel for Exponential {
+    fn latency(&mut self, _sender: i32, _target: i32, rng: &mut SimRng) -> Time {
+        to_time(-self.mean * (1.0 - rng.next_f64()).ln())
+    }
+}
+
+/// Log-normally distributed latency: `exp(mu + sigma * Z)` for a standard normal `Z`
+#[derive(Debug, Clone, Copy)]
+pub struct LogNormal {
+    pub mu: f64,
+    pub sigma: f64,
+}
+
+impl LatencyModel for LogNormal {
+    fn latency(&mut self, _sender: i32, _target: i32, rng: &mut SimRng) -> Time {
+        // Box-Muller transform
+        let u1 = 1.0 - rng.next_f64();
+        let u2 = rng.next_f64();
+        let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        to_time((self.mu + self.sigma * z).exp())
+    }
+}
+
+/// Latency sampled uniformly from a table of observed values.
+/// Repeating a value in the table makes it proportionally more likely.
+#[derive(Debug, Clone)]
+pub struct Empirical {
+    samples: Vec<Time>,
+}
+
+impl Empirical {
+    pub fn new(samples: Vec<Time>) -> Self {
+        assert!(!samples.is_empty(), "empirical latency table is empty");
+        Empirical { samples }
+    }
+}
+
+impl LatencyModel for Empirical {
+    fn latency(&mut self, _sender: i32, _target: i32, rng: &mut SimRng) -> Time {
+        *rng.choose(&self.samples).unwrap()
+    }
+}
+
+/// Per-link latency models, falling back to a default for links without one
+pub struct LinkMatrix {
+    default: Box<dyn LatencyModel>,
+    links: HashMap<(i32, i32), Box<dyn LatencyModel>>,
+}
+
+impl LinkMatrix {
+    /// Creates a matrix where every link uses `default`
+    pub fn new(default: Box<dyn LatencyModel>) -> Self {
+        LinkMatrix {
+            default,
+            links: HashMap::new(),
+        }
+    }
+
+    /// Creates a matrix of constant latencies, where `matrix[sender][target]`
+    /// is the latency from sender to target
+    pub fn from_constants(matrix: &[Vec<Time>]) -> Self {
+        let mut links = LinkMatrix::new(Box::new(Constant(0)));
+        for (sender, row) in matrix.iter().enumerate() {
+            for (target, latency) in row.iter().enumerate() {
+                links.set_link(sender as i32, target as i32, Box::new(Constant(*latency)));
+            }
+        }
+        links
+    }
+
+    /// Sets the model of the link from sender to target
+    pub fn set_link(&mut self, sender: i32, target: i32, model: Box<dyn LatencyModel>) {
+        self.links.insert((sender, target), model);
+    }
+}
+
+impl LatencyModel for LinkMatrix {
+    fn latency(&mut self, sender: i32, target: i32, rng: &mut SimRng) -> Time {
+        match self.links.get_mut(&(sender, target)) {
+            Some(model) => model.latency(sender, target, rng),
+            None => self.default.latency(sender, target, rng),
+        }
+    }
+
+    fn is_random(&self) -> bool {
+        self.default.is_random() || self.links.values().any(|model| model.is_random())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(model: &mut dyn LatencyModel, seed: u64) -> Vec<Time> {
+        let mut rng = SimRng::new(seed);
+        (0..200).map(|_| model.latency(0, 1, &mut rng)).collect()
+    }
+
+    #[test]
+    fn test_distributions() {
+        assert!(samples(&mut Constant(4), 0).iter().all(|&t| t == 4));
+        assert!(samples(&mut Uniform::new(2, 5), 0)
+            .iter()
+            .all(|t| (2..=5).contains(t)));
+        assert!(samples(&mut Uniform::new(Time::MAX - 1, Time::MAX), 0)
+            .iter()
+            .all(|&t| t >= Time::MAX - 1));
+        assert!(samples(&mut Empirical::new(vec![1, 7]), 0)
+            .iter()
+            .all(|&t| t == 1 || t == 7));
+
+        // Same seed, same latencies
+        let mut model = LogNormal { mu: 1.0, sigma: 0.5 };
+        assert_eq!(samples(&mut model, 3), samples(&mut model, 3));
+
+        let exponential = samples(&mut Exponential { mean: 10.0 }, 0);
+        let mean = exponential.iter().sum::<Time>() as f64 / exponential.len() as f64;
+        assert!((7.0..13.0).contains(&mean));
+    }
+
+    #[test]
+    #[should_panic(expected = "is empty")]
+    fn test_uniform_rejects_empty_range() {
+        Uniform::new(5, 2);
+    }
+
+    #[test]
+    fn test_link_matrix() {
+        let mut rng = SimRng::new(0);
+        let mut links = LinkMatrix::from_constants(&[vec![0, 3], vec![5, 0]]);
+        assert_eq!(links.latency(0, 1, &mut rng), 3);
+        assert_eq!(links.latency(1, 0, &mut rng), 5);
+        // Links outside the matrix use the default
+        assert_eq!(links.latency(2, 0, &mut rng), 0);
+
+        links.set_link(0, 1, Box::new(Constant(9)));
+        assert_eq!(links.latency(0, 1, &mut rng), 9);
+        assert!(!links.is_random());
+
+        // One random link makes the whole matrix random
+        links.set_link(1, 0, Box::new(Uniform::new(1, 2)));
+        assert!(links.is_random());
+    }
+}
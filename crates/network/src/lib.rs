@@ -1,93 +1,544 @@
 use async_trait::async_trait;
 use std::any::Any;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::Mutex;
 
 // TODO: This unused import is allowed becasue eventually I want to use Message
 use logging::Logger;
 #[allow(unused_imports)]
-use message::{Message, MessageString};
-use utils::{skip, ProcessEffect};
+use message::{Envelope, Message, MessageString, VectorClock};
+use utils::{skip, IdAllocator, ProcessEffect, SimRng};
+
+pub mod adversary;
+pub mod bandwidth;
+pub mod causality;
+pub mod crash;
+pub mod explore;
+pub mod faults;
+pub mod flood;
+pub mod handle;
+mod invariant;
+pub mod latency;
+mod mailbox;
+pub mod partition;
+pub mod processing;
+pub mod rpc;
+pub mod scheduler;
+pub mod simulation;
+pub mod stats;
+pub mod strategy;
+pub mod synchrony;
+pub mod topology;
+pub mod trace;
+
+pub use adversary::{Adversary, Delay, Equivocate, Outgoing, Replay, Silence};
+pub use bandwidth::{Bandwidth, MessageSizes};
+pub use causality::{CausalEvent, CausalKind, Causality};
+pub use crash::Restart;
+pub use explore::{ExplorationReport, Explorer, Violation};
+pub use faults::{FaultConfig, LinkFaults};
+pub use flood::Flooding;
+pub use handle::NetworkHandle;
+pub use invariant::{InvariantCheck, InvariantViolation};
+pub use latency::LatencyModel;
+pub use partition::{CrossingPolicy, Partition, PartitionAction, PartitionSchedule};
+pub use processing::ProcessingCosts;
+pub use rpc::{RpcClient, RpcError, RpcReply, RpcRequest, RpcResponse};
+pub use scheduler::{Event, Scheduler, Time, TimerId};
+pub use simulation::Simulation;
+pub use stats::{NetworkStats, RunLimits, RunSummary, StopReason};
+pub use strategy::{
+    CallbackStrategy, Candidate, RandomStrategy, RoundRobinStrategy, SchedulingStrategy,
+};
+pub use synchrony::{DeliveryAdversary, MaxDelay, PartialSynchrony};
+pub use topology::Topology;
+pub use trace::{Trace, TraceEntry, TraceKind};
+
+use handle::Command;
+use invariant::Invariant;
+use mailbox::Mailbox;
 
 /// Base trait for node properties
 // TODO: Use message::Message instead of MessageString
 #[async_trait]
 pub trait Node: Send + Sync + std::fmt::Debug + Any {
-    /// Initializes a Node with the given ident, environment, and network
-    fn initialize(&mut self, ident: i32, network: Arc<Mutex<Network>>);
+    /// Initializes a Node with the given ident and a handle on the network
+    fn initialize(&mut self, ident: i32, network: NetworkHandle);
 
     /// Returns the node's identifier
     fn ident(&self) -> i32;
 
-    /// Returns a reference to the network
-    fn network(&self) -> Arc<Mutex<Network>>;
+    /// Returns the node's handle on the network
+    fn network(&self) -> &NetworkHandle;
 
     /// Logs an event for this node
     async fn log(&self, event: &str, detail: &str) {
-        self.network().lock().await.log(self.ident(), event, detail);
+        self.network().log(self.ident(), event, detail);
     }
 
     /// Sends a message to a target node
     async fn send(&self, target: i32, message: Box<dyn Message>, delay: Option<u32>) -> ProcessEffect {
-        self.network().lock().await
-            .send(self.ident(), target, message, delay)
-            .await
+        self.network().send(self.ident(), target, message, delay);
+        skip().await
     }
 
-    /// Broadcasts a message to all nodes
+    /// Broadcasts a message to all neighbors
     async fn broadcast(&self, message: Box<dyn Message>, delay: Option<u32>) -> ProcessEffect {
-        self.network().lock().await.broadcast(self.ident(), message, delay).await
+        self.network().broadcast(self.ident(), message, delay);
+        skip().await
+    }
+
+    /// Sets a timer that calls `on_timer(tag)` after `after` units of virtual time
+    async fn set_timer(&self, after: Time, tag: u64) -> TimerId {
+        self.network().set_timer(self.ident(), after, tag)
+    }
+
+    /// Cancels a timer that has not fired yet
+    async fn cancel_timer(&self, timer: TimerId) {
+        self.network().cancel_timer(timer);
+    }
+
+    /// Returns true if the node handles one message at a time. Messages that
+    /// arrive while it is busy wait in a mailbox kept by the network, and are
+    /// passed straight to `handle` in arrival order.
+    fn is_sequential(&self) -> bool {
+        false
+    }
+
+    /// Returns the virtual time the node spends on a message before its
+    /// handler runs. A sequential node is busy for that long, so later
+    /// messages queue behind it; other nodes process messages concurrently.
+    fn processing_time(&self, _message: &dyn Message) -> Time {
+        0
+    }
+
+    /// Called when the node crashes. A node that restarts with its persisted
+    /// state discards the rest of its state here.
+    fn on_crash(&self) {}
+
+    /// Restarts the node after a crash with its persisted state. By default
+    /// the node's process runs again.
+    async fn recover(&self) -> ProcessEffect {
+        self.run().await
+    }
+
+    /// Returns the adversary that perturbs what this node sends, if the node
+    /// is Byzantine
+    fn adversary(&self) -> Option<&std::sync::Mutex<Box<dyn Adversary>>> {
+        None
+    }
+
+    /// Returns a digest of the node's protocol state, so that state-space
+    /// exploration can recognise states it has already seen. Nodes that
+    /// return None are never considered to be in the same state twice.
+    fn state_hash(&self) -> Option<u64> {
+        None
+    }
+
+    /// Called when a timer set by this node fires
+    async fn on_timer(&self, _tag: u64) -> ProcessEffect {
+        skip().await
     }
 
     /// Receives a message from a sender
-    async fn receive(&self, sender: i32, message: Box<dyn Message>) -> ProcessEffect {
-        self.handle(sender, message).await
+    async fn receive(&self, envelope: Envelope) -> ProcessEffect {
+        self.handle(envelope).await
     }
 
     /// Handles a received message
-    async fn handle(&self, sender: i32, message: Box<dyn Message>) -> ProcessEffect;
+    async fn handle(&self, envelope: Envelope) -> ProcessEffect;
 
     /// Runs the node
     async fn run(&self) -> ProcessEffect;
 }
 
+/// What the driver does with an event once the network lock is released
+enum Dispatch {
+    Run,
+    Recover,
+    Receive {
+        envelope: Envelope,
+    },
+    Timer {
+        tag: u64,
+    },
+    Handle {
+        envelope: Envelope,
+    },
+}
+
+/// Returns true if the event is a timer that has been cancelled
+fn is_cancelled(cancelled: &HashSet<TimerId>, event: &Event) -> bool {
+    matches!(event, Event::Timer { timer, .. } if cancelled.contains(timer))
+}
+
 /// Network simulation layer
 pub struct Network {
-    self_ref: Option<Arc<Mutex<Network>>>, // Reference to self
+    handle: NetworkHandle,
+    /// What nodes asked of the network through their handles
+    commands: UnboundedReceiver<Command>,
     nodes: Vec<Arc<dyn Node>>,      // Only needs basic Node functionality
-    delay: i64,
+    latency: Box<dyn LatencyModel>,
+    faults: FaultConfig,
+    bandwidth: Bandwidth,
+    message_sizes: Option<MessageSizes>,
+    /// When each link finishes transferring the messages queued on it
+    link_busy: HashMap<(i32, i32), Time>,
+    delivery_adversary: Option<(Box<dyn DeliveryAdversary>, PartialSynchrony)>,
+    topology: Option<Arc<Topology>>,
+    partition: Option<(Partition, CrossingPolicy)>,
+    held: Vec<(i32, Envelope)>,
+    cancelled_timers: HashSet<TimerId>,
+    crashed: HashSet<i32>,
+    mailboxes: HashMap<i32, Mailbox>,
+    stats: NetworkStats,
+    trace: Option<Trace>,
+    clocks: HashMap<i32, VectorClock>,
+    causality: Option<Causality>,
+    replay: Option<VecDeque<u64>>,
+    strategy: Option<Box<dyn SchedulingStrategy>>,
+    invariants: Vec<Invariant>,
+    current: Option<Candidate>,
+    violation: Option<InvariantViolation>,
     logger: Box<dyn Logger>,
+    scheduler: Scheduler,
+    rng: SimRng,
+    ids: IdAllocator,
+    next_message_id: u64,
 }
 
 impl Network {
-    /// Creates a new Network with optional initial nodes and delay
+    /// Creates a new Network with optional initial nodes and a latency model.
+    /// All randomness in the network is drawn from an RNG seeded with `seed`.
     pub fn new(
+        seed: u64,
         nodes: Option<Vec<Arc<dyn Node>>>,
-        delay: i64,
+        latency: Box<dyn LatencyModel>,
         logger: Box<dyn Logger>,
     ) -> Arc<Mutex<Self>> {
         logger.header();
+        let (handle, commands) = NetworkHandle::new(seed);
+        let nodes = nodes.unwrap_or_default();
+        handle.set_num_nodes(nodes.len());
         let network = Network {
-            self_ref: None,
-            nodes: nodes.unwrap_or_default(),
-            delay,
+            handle,
+            commands,
+            nodes,
+            latency,
+            faults: FaultConfig::default(),
+            bandwidth: Bandwidth::unlimited(),
+            message_sizes: None,
+            link_busy: HashMap::new(),
+            delivery_adversary: None,
+            topology: None,
+            partition: None,
+            held: Vec::new(),
+            cancelled_timers: HashSet::new(),
+            crashed: HashSet::new(),
+            mailboxes: HashMap::new(),
+            stats: NetworkStats::default(),
+            trace: None,
+            clocks: HashMap::new(),
+            causality: None,
+            replay: None,
+            strategy: None,
+            invariants: Vec::new(),
+            current: None,
+            violation: None,
             logger,
+            scheduler: Scheduler::new(),
+            rng: SimRng::new(seed),
+            ids: IdAllocator::new(),
+            next_message_id: 0,
         };
-        let arc_mutex = Arc::new(Mutex::new(network));
-        
-        // Set the self reference
-        let self_ref = arc_mutex.clone();
-        // Need to set self_ref in a separate block to avoid borrow issues
-        if let Ok(mut guard) = arc_mutex.try_lock() {
-            guard.self_ref = Some(self_ref.clone());
-        }
-        
-        arc_mutex
+        Arc::new(Mutex::new(network))
+    }
+
+    /// Returns a handle on the network, as nodes get when they are added
+    pub fn handle(&self) -> NetworkHandle {
+        self.handle.clone()
     }
 
     /// Logs an event for a node
     pub fn log(&self, ident: i32, event: &str, detail: &str) {
-        self.logger.log(ident, event, detail);
+        self.logger.log(self.now(), ident, event, detail);
+    }
+
+    /// Returns the current virtual time
+    pub fn now(&self) -> Time {
+        self.scheduler.now()
+    }
+
+    /// Returns the counters kept since the network was created
+    pub fn stats(&self) -> NetworkStats {
+        self.stats
+    }
+
+    /// Returns true if no events are pending and no node is busy
+    pub fn is_quiescent(&self) -> bool {
+        self.scheduler.is_empty() && !self.mailboxes.values().any(Mailbox::is_busy)
+    }
+
+    /// Returns the number of messages waiting in the mailbox of a sequential node
+    pub fn mailbox_len(&self, ident: i32) -> usize {
+        self.mailboxes.get(&ident).map_or(0, Mailbox::len)
+    }
+
+    /// Returns true if a sequential node has messages it has not finished handling
+    pub fn is_busy(&self, ident: i32) -> bool {
+        self.mailboxes.get(&ident).is_some_and(Mailbox::is_busy)
+    }
+
+    /// Returns the simulation RNG. Nodes draw their randomness from the
+    /// streams `NetworkHandle::rng` derives from the same seed.
+    pub fn rng(&mut self) -> &mut SimRng {
+        &mut self.rng
+    }
+
+    /// Returns true if sending a message may draw from the RNG for its latency
+    /// or faults, so that processing the same events in another order can
+    /// lead to different states. Tie-breakers are drawn from the RNG as well,
+    /// but only order events due at the same time.
+    pub fn is_random(&self) -> bool {
+        self.latency.is_random() || !self.faults.is_reliable()
+    }
+
+    /// Replaces the latency model; messages already in flight keep their delays
+    pub fn set_latency_model(&mut self, latency: Box<dyn LatencyModel>) {
+        self.latency = latency;
+    }
+
+    /// Replaces the link bandwidths; transfers already queued are unaffected
+    pub fn set_bandwidth(&mut self, bandwidth: Bandwidth) {
+        self.bandwidth = bandwidth;
+    }
+
+    /// Sizes messages with `sizes` for bandwidth accounting, and counts the
+    /// bytes sent even if no link is limited
+    pub fn set_message_sizes(&mut self, sizes: MessageSizes) {
+        self.message_sizes = Some(sizes);
+    }
+
+    /// Returns the size of a message in bytes as the network counts it
+    pub fn message_size(&self, message: &dyn Message) -> usize {
+        match &self.message_sizes {
+            Some(sizes) => sizes.size(message),
+            None => MessageSizes::estimate(message),
+        }
+    }
+
+    /// Returns true if messages are sized as they are sent: when a link is
+    /// limited or message sizes are set. Otherwise sending skips sizing.
+    fn counts_bytes(&self) -> bool {
+        self.message_sizes.is_some() || !self.bandwidth.is_unlimited()
+    }
+
+    /// Restricts which nodes can send to each other. Without a topology every
+    /// node can reach every other node.
+    pub fn set_topology(&mut self, topology: Topology) {
+        let topology = Arc::new(topology);
+        self.handle.set_topology(Some(topology.clone()));
+        self.topology = Some(topology);
+    }
+
+    pub fn topology(&self) -> Option<&Topology> {
+        self.topology.as_deref()
+    }
+
+    /// Returns the nodes `ident` can send to, in ident order
+    pub fn neighbors(&self, ident: i32) -> Vec<i32> {
+        match &self.topology {
+            Some(topology) => topology.neighbors(ident),
+            None => (0..self.num_nodes() as i32)
+                .filter(|target| *target != ident)
+                .collect(),
+        }
+    }
+
+    /// Replaces the fault configuration; messages already in flight are unaffected
+    pub fn set_faults(&mut self, faults: FaultConfig) {
+        self.faults = faults;
+    }
+
+    /// Lets an adversary choose the delay of every message in flight, within
+    /// the bounds of the partial synchrony `model`. The adversary has the
+    /// last word on delays, after the latency model and injected faults.
+    pub fn set_delivery_adversary(
+        &mut self,
+        adversary: Box<dyn DeliveryAdversary>,
+        model: PartialSynchrony,
+    ) {
+        self.delivery_adversary = Some((adversary, model));
+    }
+
+    /// Returns the simulation's ID allocator, for protocol objects such as
+    /// blocks and transactions that need identifiers
+    pub fn ids(&mut self) -> &mut IdAllocator {
+        &mut self.ids
+    }
+
+    /// Starts recording a trace of every send, delivery, drop, timer firing
+    /// and other processed event
+    pub fn record_trace(&mut self) {
+        self.trace.get_or_insert_with(Trace::new);
+    }
+
+    /// Returns the trace recorded so far, if recording
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// Stops recording and returns the recorded trace
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    /// Starts recording the sends and receives of the run with their vector
+    /// clocks, for happens-before analysis
+    pub fn record_causality(&mut self) {
+        self.causality.get_or_insert_with(Causality::new);
+    }
+
+    /// Returns the causal history recorded so far, if recording
+    pub fn causality(&self) -> Option<&Causality> {
+        self.causality.as_ref()
+    }
+
+    /// Stops recording and returns the recorded causal history
+    pub fn take_causality(&mut self) -> Option<Causality> {
+        self.causality.take()
+    }
+
+    /// Returns the vector clock of a node. The network counts every message
+    /// a node sends or handles.
+    pub fn clock(&self, ident: i32) -> VectorClock {
+        self.clocks.get(&ident).cloned().unwrap_or_default()
+    }
+
+    /// Replays a recorded trace: events are processed in the order the trace
+    /// processed them instead of in scheduler order. If the run diverges from
+    /// the trace, the divergence is logged and scheduling returns to normal.
+    pub fn replay(&mut self, trace: &Trace) {
+        self.replay = Some(trace.processing_order().into());
+    }
+
+    /// Returns true while events are processed in the order of a replayed trace
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Lets a strategy choose which of several simultaneous events is
+    /// processed next, instead of the seeded tie-breakers
+    pub fn set_strategy(&mut self, strategy: Box<dyn SchedulingStrategy>) {
+        self.strategy = Some(strategy);
+    }
+
+    /// Returns the pending events that could happen next if the network were
+    /// asynchronous, i.e. if any of them could overtake any other
+    pub fn pending_candidates(&self) -> Vec<Candidate> {
+        self.scheduler
+            .pending()
+            .into_iter()
+            .filter(|(_, _, event)| !is_cancelled(&self.cancelled_timers, event))
+            .map(|(seq, time, event)| Candidate::new(seq, time, event))
+            .collect()
+    }
+
+    /// Returns a digest of the state of the protocol: the state of every node,
+    /// the pending events, queued and held messages and the partition.
+    /// Virtual time and sequence numbers are left out, so states reached by
+    /// different interleavings compare equal. Returns None if a node does not
+    /// expose its state.
+    pub fn state_hash(&self) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        for node in &self.nodes {
+            node.state_hash()?.hash(&mut hasher);
+        }
+        let mut pending: Vec<(i32, i32, String)> = self
+            .pending_candidates()
+            .into_iter()
+            .map(|candidate| (candidate.sender, candidate.target, candidate.description))
+            .collect();
+        pending.sort();
+        pending.hash(&mut hasher);
+        let mut mailboxes: Vec<(i32, Vec<String>)> = self
+            .mailboxes
+            .iter()
+            .map(|(ident, mailbox)| {
+                let messages = mailbox
+                    .iter()
+                    .map(|envelope| format!("{} {:?}", envelope.sender, envelope.message))
+                    .collect();
+                (*ident, messages)
+            })
+            .collect();
+        mailboxes.sort();
+        mailboxes.hash(&mut hasher);
+        let mut held: Vec<String> = self
+            .held
+            .iter()
+            .map(|(target, envelope)| {
+                format!("{} {} {:?}", envelope.sender, target, envelope.message)
+            })
+            .collect();
+        held.sort();
+        held.hash(&mut hasher);
+        format!("{:?}", self.partition).hash(&mut hasher);
+        let mut crashed: Vec<i32> = self.crashed.iter().copied().collect();
+        crashed.sort();
+        crashed.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    /// Registers a predicate over the network and its nodes that must hold
+    /// after every processed event. Once one fails, the network stops
+    /// processing events. Registering an invariant starts recording a trace,
+    /// so that the violation can be reported with the run leading to it.
+    pub fn add_invariant<F>(&mut self, name: &str, check: F)
+    where
+        F: Fn(&Network) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.record_trace();
+        self.invariants.push(Invariant {
+            name: name.to_string(),
+            check: Box::new(check),
+        });
+    }
+
+    /// Returns the first invariant violation, if one has occurred
+    pub fn violation(&self) -> Option<&InvariantViolation> {
+        self.violation.as_ref()
+    }
+
+    /// Evaluates the invariants after the event currently being processed
+    fn check_invariants(&mut self) {
+        let Some(event) = self.current.take() else {
+            return;
+        };
+        let failed = self.invariants.iter().find_map(|invariant| {
+            (invariant.check)(self)
+                .err()
+                .map(|message| (invariant.name.clone(), message))
+        });
+        if let Some((invariant, message)) = failed {
+            let violation = InvariantViolation {
+                invariant,
+                message,
+                event,
+                trace: self.trace.clone().unwrap_or_default(),
+            };
+            self.log(-1, "violated", &violation.to_string());
+            self.violation = Some(violation);
+        }
+    }
+
+    /// Returns the number of events waiting to be processed
+    pub fn pending_events(&self) -> usize {
+        self.scheduler.len()
     }
 
     /// Returns the number of nodes
@@ -104,40 +555,45 @@ impl Network {
     pub fn add_node(&mut self, mut node: Arc<dyn Node>) {
         let ident = self.num_nodes() as i32;
         self.log(ident, "add_node", "adding node");
-        if let Some(network_ref) = &self.self_ref {
-            Arc::get_mut(&mut node)
-                .unwrap()
-                .initialize(ident, network_ref.clone());
-            self.nodes.push(node);
-        }
+        Arc::get_mut(&mut node)
+            .unwrap()
+            .initialize(ident, self.handle.clone());
+        self.nodes.push(node);
+        self.handle.set_num_nodes(self.nodes.len());
     }
 
-    /// Starts a specific node
-    pub async fn start_node(&self, ident: i32) {
-        if let Some(node) = self.node(ident) {
-            self.log(ident, "start", &format!("{:?}", node));
-            // Clone the node before spawning
-            let node = node.clone();
-            tokio::spawn(async move { node.run().await });
+    /// Schedules a specific node to start at the current virtual time
+    pub async fn start_node(&mut self, ident: i32) {
+        if self.node(ident).is_some() {
+            self.schedule(0, Event::Start { ident });
         }
     }
 
-    /// Starts all nodes
-    pub async fn start_all_nodes(&self) {
+    /// Schedules all nodes to start at the current virtual time
+    pub async fn start_all_nodes(&mut self) {
         for i in 0..self.num_nodes() {
             self.start_node(i as i32).await;
         }
     }
 
+    /// Returns the delay of a message from sender to target: the override if
+    /// given, otherwise drawn from the latency model
+    fn delay(&mut self, sender: i32, target: i32, delay: Option<u32>) -> Time {
+        match delay {
+            Some(delay) => Time::from(delay),
+            None => self.latency.latency(sender, target, &mut self.rng),
+        }
+    }
+
     /// Sends a message from one node to another
     pub async fn send(
-        &self,
+        &mut self,
         sender: i32,
         target: i32,
         message: Box<dyn Message>,
         delay: Option<u32>,
     ) -> ProcessEffect {
-        let delay = delay.unwrap_or(self.delay as u32) as i64;
+        let delay = self.delay(sender, target, delay);
 
         self.log(
             sender,
@@ -145,66 +601,818 @@ impl Network {
             &format!("to {:2} with delay {:2}: {:?}", target, delay, message),
         );
 
-        // Spawn convey process
-        let network = self.self_ref.clone().unwrap();
-        let task =
-            tokio::spawn(async move { network.lock().await.convey(delay, sender, target, message).await });
-
-        // Wait for the convey to complete
-        if let Err(e) = task.await {
-            eprintln!("Task failed: {}", e);
-        };
+        self.convey(delay, sender, target, message, 1);
+        skip().await
+    }
 
+    /// Passes on a message received in `envelope` to `target`, one hop
+    /// further than it came
+    pub async fn relay(
+        &mut self,
+        sender: i32,
+        target: i32,
+        envelope: Envelope,
+        delay: Option<u32>,
+    ) -> ProcessEffect {
+        let delay = self.delay(sender, target, delay);
+        let message = envelope.message;
+        self.log(
+            sender,
+            "relay",
+            &format!("to {:2} with delay {:2}: {:?}", target, delay, message),
+        );
+        self.convey(delay, sender, target, message, envelope.hops + 1);
         skip().await
     }
 
-    /// Broadcasts a message to all nodes
+    /// Broadcasts a message to all neighbors of the sender
     pub async fn broadcast(
-        &self,
+        &mut self,
         sender: i32,
         message: Box<dyn Message>,
         delay: Option<u32>,
     ) -> ProcessEffect {
-        let delay = delay.unwrap_or(self.delay as u32) as i64;
+        self.log(sender, "broadcast", &format!("to *: {:?}", message));
 
-        self.log(
-            sender,
-            "broadcast",
-            &format!("to * with delay {:2}: {:?}", delay, message),
-        );
+        // Schedule a delivery to each neighbor, in ident order
+        for target in self.neighbors(sender) {
+            let delay = self.delay(sender, target, delay);
+            self.convey(delay, sender, target, message.box_clone(), 1);
+        }
 
-        // Spawn convey process for each node
-        for target in 0..self.num_nodes() as i32 {
-            if target != sender {
-                let network = self.self_ref.clone().unwrap();
-                let message = message.box_clone();
-                tokio::spawn(async move { network.lock().await.convey(delay, sender, target, message).await });
+        skip().await
+    }
+
+    /// Schedules an event after delay, breaking ties with the simulation RNG
+    fn schedule(&mut self, delay: Time, event: Event) -> u64 {
+        let tiebreak = self.rng.next_u64();
+        self.scheduler.schedule(delay, tiebreak, event)
+    }
+
+    /// Adds an entry to the trace if one is being recorded. The message is
+    /// only formatted when recording.
+    fn record(
+        &mut self,
+        kind: TraceKind,
+        seq: Option<u64>,
+        sender: i32,
+        target: i32,
+        message: impl FnOnce() -> String,
+    ) {
+        let time = self.now();
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEntry {
+                time,
+                kind,
+                seq,
+                sender,
+                target,
+                message: message(),
+            });
+        }
+    }
+
+    /// Advances the vector clock of a node for a send or receive of message
+    /// `id` and records the event if recording, returning the new clock
+    fn tick(&mut self, ident: i32, kind: CausalKind, id: u64, peer: i32) -> VectorClock {
+        let clock = self.clocks.entry(ident).or_default();
+        clock.increment(ident);
+        let clock = clock.clone();
+        let time = self.now();
+        if let Some(causality) = &mut self.causality {
+            causality.push(CausalEvent {
+                time,
+                node: ident,
+                kind,
+                message_id: id,
+                peer,
+                clock: clock.clone(),
+            });
+        }
+        clock
+    }
+
+    /// Merges the clock carried by a message into its target's when the
+    /// target handles it
+    fn observe(&mut self, target: i32, envelope: &Envelope) {
+        if let Some(clock) = &envelope.clock {
+            self.clocks.entry(target).or_default().merge(clock);
+        }
+        self.tick(target, CausalKind::Receive, envelope.id, envelope.sender);
+    }
+
+    /// Takes the next event to process: the next one from the replayed trace
+    /// while replaying, the strategy's choice among the events due next if
+    /// one is set, otherwise the earliest pending one
+    fn next_event(&mut self) -> Option<(u64, Event)> {
+        if let Some(replay) = &mut self.replay {
+            match replay.pop_front() {
+                Some(seq) => {
+                    if let Some(event) = self.scheduler.pop_seq(seq) {
+                        return Some((seq, event));
+                    }
+                    self.log(
+                        -1,
+                        "diverge",
+                        &format!("event {} of the trace is not pending", seq),
+                    );
+                    self.replay = None;
+                }
+                None => self.replay = None,
             }
         }
+        if let Some(strategy) = &mut self.strategy {
+            let time = self.scheduler.peek_time().unwrap_or_default();
+            // Cancelled timers are skipped when popped, so they are not a choice
+            let cancelled = &self.cancelled_timers;
+            let candidates: Vec<Candidate> = self
+                .scheduler
+                .due()
+                .into_iter()
+                .filter(|(_, event)| !is_cancelled(cancelled, event))
+                .map(|(seq, event)| Candidate::new(seq, time, event))
+                .collect();
+            if candidates.len() > 1 {
+                let index = strategy.choose(&candidates, &mut self.rng);
+                let seq = candidates[index.min(candidates.len() - 1)].seq;
+                let event = self.scheduler.pop_seq(seq)?;
+                return Some((seq, event));
+            }
+        }
+        self.scheduler.pop()
+    }
 
-        skip().await
+    /// Schedules the delivery of a message from sender to target after delay,
+    /// having travelled `hops` links. Nothing is sent if the topology has no
+    /// link between them. If the sender is Byzantine, its adversary decides
+    /// what is sent instead.
+    fn convey(
+        &mut self,
+        delay: Time,
+        sender: i32,
+        target: i32,
+        message: Box<dyn Message>,
+        hops: u32,
+    ) {
+        if let Some(topology) = &self.topology {
+            if !topology.are_neighbors(sender, target) {
+                self.log(sender, "no link", &format!("to {:2}: {:?}", target, message));
+                return;
+            }
+        }
+        let node = self.node(sender).cloned();
+        let Some(adversary) = node.as_ref().and_then(|node| node.adversary()) else {
+            self.transmit(delay, 0, sender, target, message, hops);
+            return;
+        };
+        let detail = format!("to {:2}: {:?}", target, message);
+        let outgoing = adversary
+            .lock()
+            .expect("adversary poisoned")
+            .outgoing(self.now(), target, message);
+        if outgoing.is_empty() {
+            self.log(sender, "withhold", &detail);
+        }
+        for Outgoing { message, delay: extra } in outgoing {
+            self.log(
+                sender,
+                "byzantine",
+                &format!("to {:2} delayed by {:2}: {:?}", target, extra, message),
+            );
+            self.transmit(delay, extra, sender, target, message, hops);
+        }
     }
 
-    /// Conveys a message from sender to target after delay
-    async fn convey(
-        &self,
-        delay: i64,
+    /// Schedules the delivery of a message from sender to target, `delay`
+    /// after the sender has held it for `hold` and the link has transferred
+    /// it, injecting the faults configured for the link. Each injected fault
+    /// is logged. A delivery adversary only chooses the propagation delay,
+    /// never the time the message takes to leave its sender.
+    fn transmit(
+        &mut self,
+        delay: Time,
+        hold: Time,
         sender: i32,
         target: i32,
         message: Box<dyn Message>,
-    ) -> ProcessEffect {
-        tokio::time::sleep(tokio::time::Duration::from_secs(delay as u64)).await;
+        hops: u32,
+    ) {
+        self.stats.messages_sent += 1;
+        let mut departure = hold;
+        if self.counts_bytes() {
+            let size = self.message_size(message.as_ref());
+            self.stats.bytes_sent += size as u64;
+            departure += self.enqueue(sender, target, size);
+        }
+        let id = self.next_message_id;
+        self.next_message_id += 1;
+        let clock = self.tick(sender, CausalKind::Send, id, target);
+        let faults = self.faults.link(sender, target);
+        // Only draw from the RNG for faults that are enabled, so that a reliable
+        // link leaves the random stream untouched
+        if faults.drop_probability > 0.0 && self.rng.gen_bool(faults.drop_probability) {
+            self.log(sender, "drop", &format!("to {:2}: {:?}", target, message));
+            self.record(TraceKind::Drop, None, sender, target, || {
+                format!("{:?}", message)
+            });
+            self.stats.messages_dropped += 1;
+            return;
+        }
+        let copies = if faults.duplicate_probability > 0.0
+            && self.rng.gen_bool(faults.duplicate_probability)
+        {
+            self.log(sender, "duplicate", &format!("to {:2}: {:?}", target, message));
+            2
+        } else {
+            1
+        };
+
+        let mut envelope = Envelope::new(id, sender, self.now(), message);
+        envelope.hops = hops;
+        envelope.clock = Some(clock);
+        for _ in 0..copies {
+            let message = &envelope.message;
+            let mut delay = delay;
+            if faults.reorder_bound > 0 {
+                let extra = self.rng.gen_range_inclusive(0..=faults.reorder_bound);
+                if extra > 0 {
+                    self.log(
+                        sender,
+                        "reorder",
+                        &format!("to {:2} delayed by {:2}: {:?}", target, extra, message),
+                    );
+                }
+                // A latency that saturated at the end of time stays there
+                delay = delay.saturating_add(extra);
+            }
+            if let Some((adversary, model)) = &mut self.delivery_adversary {
+                let now = self.scheduler.now();
+                let max_delay = model.max_delay(now.saturating_add(departure));
+                let chosen = adversary
+                    .delay(now, sender, target, message.as_ref(), delay, max_delay)
+                    .min(max_delay);
+                if chosen != delay {
+                    self.log(
+                        sender,
+                        "adversary",
+                        &format!(
+                            "to {:2} delayed {:2} instead of {:2}: {:?}",
+                            target, chosen, delay, message
+                        ),
+                    );
+                }
+                delay = chosen;
+            }
+            let delay = departure.saturating_add(delay);
+            let seq = self.schedule(
+                delay,
+                Event::Deliver {
+                    target,
+                    envelope: envelope.clone(),
+                    delay,
+                },
+            );
+            self.record(TraceKind::Send, Some(seq), sender, target, || {
+                format!("{:?}", message)
+            });
+        }
+    }
 
+    /// Queues `size` bytes on the link from sender to target. Returns how
+    /// long it takes until they are through: the wait for the messages ahead
+    /// of them plus their own transfer time.
+    fn enqueue(&mut self, sender: i32, target: i32, size: usize) -> Time {
+        let transfer = self.bandwidth.transfer_time(sender, target, size);
+        if transfer == 0 {
+            return 0;
+        }
+        let now = self.now();
+        let busy = self.link_busy.entry((sender, target)).or_insert(now);
+        let start = (*busy).max(now);
+        *busy = start + transfer;
+        let wait = start - now;
         self.log(
-            target,
-            "receive",
-            &format!("from {:2} with delay {:2}: {:?}", sender, delay, message),
+            sender,
+            "transfer",
+            &format!(
+                "to {:2}: {} bytes in {:2} after waiting {:2}",
+                target, size, transfer, wait
+            ),
         );
+        wait + transfer
+    }
 
-        if let Some(node) = self.node(target) {
-            node.receive(sender, message).await
-        } else {
-            skip().await
+    /// Sets a timer that fires `on_timer(tag)` on node `ident` after `after`
+    /// units of virtual time
+    pub fn set_timer(&mut self, ident: i32, after: Time, tag: u64) -> TimerId {
+        let timer = self.handle.next_timer();
+        self.schedule(after, Event::Timer { ident, tag, timer });
+        timer
+    }
+
+    /// Cancels a timer; cancelling a timer that already fired has no effect
+    pub fn cancel_timer(&mut self, timer: TimerId) {
+        self.cancelled_timers.insert(timer);
+    }
+
+    /// Applies what nodes asked of the network through their handles since
+    /// the last flush, in the order they asked
+    async fn flush(&mut self) {
+        while let Ok(command) = self.commands.try_recv() {
+            match command {
+                Command::Log {
+                    ident,
+                    event,
+                    detail,
+                } => self.log(ident, &event, &detail),
+                Command::Send {
+                    sender,
+                    target,
+                    message,
+                    delay,
+                } => {
+                    self.send(sender, target, message, delay).await;
+                }
+                Command::Broadcast {
+                    sender,
+                    message,
+                    delay,
+                } => {
+                    self.broadcast(sender, message, delay).await;
+                }
+                Command::Relay {
+                    sender,
+                    target,
+                    envelope,
+                    delay,
+                } => {
+                    self.relay(sender, target, envelope, delay).await;
+                }
+                Command::SetTimer {
+                    ident,
+                    after,
+                    tag,
+                    timer,
+                } => {
+                    self.schedule(after, Event::Timer { ident, tag, timer });
+                }
+                Command::CancelTimer(timer) => self.cancel_timer(timer),
+            }
+        }
+    }
+
+    /// Splits the network; messages crossing the partition are dropped or held
+    /// according to `policy` until it heals
+    pub fn split(&mut self, partition: Partition, policy: CrossingPolicy) {
+        self.log(-1, "split", &format!("{:?} ({:?})", partition, policy));
+        self.partition = Some((partition, policy));
+    }
+
+    /// Heals the current partition, releasing any held messages immediately
+    pub fn heal(&mut self) {
+        self.log(-1, "heal", "");
+        self.partition = None;
+        for (target, envelope) in std::mem::take(&mut self.held) {
+            let detail = format!("from {:2}: {:?}", envelope.sender, envelope.message);
+            self.log(target, "release", &detail);
+            self.schedule(
+                0,
+                Event::Deliver {
+                    target,
+                    envelope,
+                    delay: 0,
+                },
+            );
+        }
+    }
+
+    /// Crashes a node. Deliveries in flight to it and messages in its mailbox
+    /// are dropped, its timers are discarded, and messages sent to it are
+    /// dropped until it recovers.
+    pub fn crash(&mut self, ident: i32) {
+        if self.node(ident).is_none() || !self.crashed.insert(ident) {
+            return;
+        }
+        self.log(ident, "crash", "");
+        let removed = self.scheduler.remove_where(|event| match event {
+            Event::Deliver { target, .. } => *target == ident,
+            Event::Start { ident: at }
+            | Event::Process { ident: at }
+            | Event::Processed { ident: at, .. }
+            | Event::Timer { ident: at, .. } => *at == ident,
+            _ => false,
+        });
+        let mut lost: Vec<Envelope> = Vec::new();
+        for (_, event) in removed {
+            match event {
+                Event::Timer { timer, .. } => {
+                    self.cancelled_timers.remove(&timer);
+                }
+                Event::Deliver { envelope, .. } => {
+                    // Never reached the node, unlike the messages it was processing
+                    self.stats.messages_dropped += 1;
+                    lost.push(envelope);
+                }
+                Event::Processed { envelope, .. } => lost.push(envelope),
+                _ => {}
+            }
+        }
+        if let Some(mut mailbox) = self.mailboxes.remove(&ident) {
+            lost.extend(std::iter::from_fn(|| mailbox.pop()));
+        }
+        let held = std::mem::take(&mut self.held);
+        for (target, envelope) in held {
+            if target == ident {
+                self.stats.messages_dropped += 1;
+                lost.push(envelope);
+            } else {
+                self.held.push((target, envelope));
+            }
+        }
+        for Envelope {
+            sender, message, ..
+        } in lost
+        {
+            self.log(ident, "drop", &format!("from {:2}: {:?}", sender, message));
+            self.record(TraceKind::Drop, None, sender, ident, || {
+                format!("{:?}", message)
+            });
+        }
+        if let Some(node) = self.node(ident) {
+            node.on_crash();
+        }
+    }
+
+    /// Returns true if a node has crashed and not recovered yet
+    pub fn is_crashed(&self, ident: i32) -> bool {
+        self.crashed.contains(&ident)
+    }
+
+    /// Schedules a node to crash at virtual time `at`, or immediately if that
+    /// has passed
+    pub fn schedule_crash(&mut self, at: Time, ident: i32) {
+        let delay = at.saturating_sub(self.now());
+        self.schedule(delay, Event::Crash { ident });
+    }
+
+    /// Schedules a crashed node to restart at virtual time `at`, or
+    /// immediately if that has passed. Nodes that are not crashed by then
+    /// are left alone.
+    pub fn schedule_recover(&mut self, at: Time, ident: i32, restart: Restart) {
+        let delay = at.saturating_sub(self.now());
+        self.schedule(delay, Event::Recover { ident, restart });
+    }
+
+    /// Restarts a crashed node at the current virtual time
+    pub fn recover(&mut self, ident: i32, restart: Restart) {
+        self.schedule_recover(self.now(), ident, restart);
+    }
+
+    /// Schedules the actions of a partition schedule at their virtual times.
+    /// Actions whose time has already passed happen immediately.
+    pub fn schedule_partitions(&mut self, schedule: &PartitionSchedule) {
+        for (at, action) in schedule.actions() {
+            let delay = at.saturating_sub(self.now());
+            self.schedule(delay, Event::Partition(action.clone()));
+        }
+    }
+
+    /// Called when a sequential node finished handling a message; schedules
+    /// the next one in its mailbox, if any
+    fn finish_handling(&mut self, ident: i32) {
+        let more = self.mailboxes.get_mut(&ident).is_some_and(Mailbox::finish);
+        if let (true, Some(node)) = (more, self.node(ident).cloned()) {
+            self.schedule_processing(&node);
+        }
+    }
+
+    /// Schedules a sequential node to handle the next message in its mailbox
+    /// once it has spent its processing time on it
+    fn schedule_processing(&mut self, node: &Arc<dyn Node>) {
+        let ident = node.ident();
+        let cost = self
+            .mailboxes
+            .get(&ident)
+            .and_then(Mailbox::peek)
+            .map_or(0, |envelope| node.processing_time(envelope.message.as_ref()));
+        self.schedule(cost, Event::Process { ident });
+    }
+
+    /// Applies an event to the network, returning the node it must be
+    /// dispatched to, if any
+    fn dispatch(&mut self, seq: u64, event: Event) -> Option<(Arc<dyn Node>, Dispatch)> {
+        match event {
+            Event::Start { ident } => {
+                if self.crashed.contains(&ident) {
+                    return None;
+                }
+                let node = self.node(ident)?.clone();
+                self.log(ident, "start", &format!("{:?}", node));
+                self.record(TraceKind::Start, Some(seq), ident, ident, String::new);
+                Some((node, Dispatch::Run))
+            }
+            Event::Deliver {
+                target,
+                mut envelope,
+                delay,
+            } => {
+                let sender = envelope.sender;
+                let message = &envelope.message;
+                if self.crashed.contains(&target) {
+                    self.log(target, "drop", &format!("from {:2}: {:?}", sender, message));
+                    self.record(TraceKind::Drop, Some(seq), sender, target, || {
+                        format!("{:?}", message)
+                    });
+                    self.stats.messages_dropped += 1;
+                    return None;
+                }
+                let crossing = self
+                    .partition
+                    .as_ref()
+                    .filter(|(partition, _)| partition.separates(sender, target))
+                    .map(|(_, policy)| *policy);
+                if let Some(policy) = crossing {
+                    let detail = format!("from {:2}: {:?}", sender, message);
+                    match policy {
+                        CrossingPolicy::Drop => {
+                            self.log(target, "drop", &detail);
+                            self.record(TraceKind::Drop, Some(seq), sender, target, || {
+                                format!("{:?}", message)
+                            });
+                            self.stats.messages_dropped += 1;
+                        }
+                        CrossingPolicy::Hold => {
+                            self.log(target, "hold", &detail);
+                            self.record(TraceKind::Hold, Some(seq), sender, target, || {
+                                format!("{:?}", message)
+                            });
+                            self.held.push((target, envelope));
+                        }
+                    }
+                    return None;
+                }
+                self.stats.messages_delivered += 1;
+                self.log(
+                    target,
+                    "receive",
+                    &format!("from {:2} with delay {:2}: {:?}", sender, delay, message),
+                );
+                self.record(TraceKind::Deliver, Some(seq), sender, target, || {
+                    format!("{:?}", message)
+                });
+                envelope.delivered_at = self.now();
+                let node = self.node(target)?.clone();
+                if node.is_sequential() {
+                    let mailbox = self.mailboxes.entry(target).or_default();
+                    if mailbox.push(envelope) {
+                        self.schedule_processing(&node);
+                    }
+                    return None;
+                }
+                let cost = node.processing_time(envelope.message.as_ref());
+                if cost > 0 {
+                    self.schedule(
+                        cost,
+                        Event::Processed {
+                            ident: target,
+                            envelope,
+                        },
+                    );
+                    return None;
+                }
+                self.observe(target, &envelope);
+                Some((node, Dispatch::Receive { envelope }))
+            }
+            Event::Processed { ident, envelope } => {
+                let node = self.node(ident)?.clone();
+                let (sender, message) = (envelope.sender, &envelope.message);
+                self.log(ident, "handle", &format!("from {:2}: {:?}", sender, message));
+                self.record(TraceKind::Handle, Some(seq), sender, ident, || {
+                    format!("{:?}", message)
+                });
+                self.observe(ident, &envelope);
+                Some((node, Dispatch::Receive { envelope }))
+            }
+            Event::Process { ident } => {
+                let node = self.node(ident)?.clone();
+                let envelope = self.mailboxes.get_mut(&ident)?.pop()?;
+                let (sender, message) = (envelope.sender, &envelope.message);
+                self.log(ident, "handle", &format!("from {:2}: {:?}", sender, message));
+                self.record(TraceKind::Handle, Some(seq), sender, ident, || {
+                    format!("{:?}", message)
+                });
+                self.observe(ident, &envelope);
+                Some((node, Dispatch::Handle { envelope }))
+            }
+            Event::Timer { ident, tag, .. } => {
+                let node = self.node(ident)?.clone();
+                self.log(ident, "timer", &format!("tag {}", tag));
+                self.record(TraceKind::Timer, Some(seq), ident, ident, || {
+                    format!("tag {}", tag)
+                });
+                Some((node, Dispatch::Timer { tag }))
+            }
+            Event::Partition(PartitionAction::Split(partition, policy)) => {
+                self.record(TraceKind::Partition, Some(seq), -1, -1, || {
+                    format!("split {:?} ({:?})", partition, policy)
+                });
+                self.split(partition, policy);
+                None
+            }
+            Event::Crash { ident } => {
+                self.record(TraceKind::Crash, Some(seq), ident, ident, String::new);
+                self.crash(ident);
+                None
+            }
+            Event::Recover { ident, restart } => {
+                self.record(TraceKind::Recover, Some(seq), ident, ident, || {
+                    format!("{:?}", restart)
+                });
+                if !self.crashed.remove(&ident) {
+                    return None;
+                }
+                match restart {
+                    Restart::Persisted => {
+                        self.log(ident, "recover", "with persisted state");
+                        let node = self.node(ident)?.clone();
+                        Some((node, Dispatch::Recover))
+                    }
+                    Restart::Fresh(mut node) => {
+                        self.log(ident, "recover", "from scratch");
+                        node.initialize(ident, self.handle.clone());
+                        let node: Arc<dyn Node> = Arc::from(node);
+                        *self.nodes.get_mut(ident as usize)? = node.clone();
+                        Some((node, Dispatch::Run))
+                    }
+                }
+            }
+            Event::Partition(PartitionAction::Heal) => {
+                self.record(TraceKind::Partition, Some(seq), -1, -1, || {
+                    "heal".to_string()
+                });
+                self.heal();
+                None
+            }
+        }
+    }
+
+    /// Processes the next pending event, advancing the virtual clock to its time,
+    /// then evaluates the invariants.
+    ///
+    /// The network lock is released before the event reaches its node; what
+    /// the node sends through its handle is applied once it returns.
+    /// Returns false if there was no event to process or an invariant has
+    /// been violated.
+    pub async fn step(network: &Arc<Mutex<Network>>) -> bool {
+        let dispatch = {
+            let mut network = network.lock().await;
+            network.flush().await;
+            if network.violation.is_some() {
+                return false;
+            }
+            let Some((seq, event)) = network.next_event() else {
+                return false;
+            };
+            network.take(seq, event)
+        };
+        Self::deliver(network, dispatch).await;
+        let mut network = network.lock().await;
+        network.flush().await;
+        network.check_invariants();
+        true
+    }
+
+    /// Processes the pending event with sequence number `seq` ahead of any
+    /// other, advancing the virtual clock to its time if that is later.
+    /// Returns false if no such event is pending or an invariant has been violated.
+    pub async fn step_event(network: &Arc<Mutex<Network>>, seq: u64) -> bool {
+        let dispatch = {
+            let mut network = network.lock().await;
+            network.flush().await;
+            if network.violation.is_some() {
+                return false;
+            }
+            let Some(event) = network.scheduler.pop_seq(seq) else {
+                return false;
+            };
+            network.take(seq, event)
+        };
+        Self::deliver(network, dispatch).await;
+        let mut network = network.lock().await;
+        network.flush().await;
+        network.check_invariants();
+        true
+    }
+
+    /// Accounts for an event taken off the queue and dispatches it, unless it
+    /// is a cancelled timer
+    fn take(&mut self, seq: u64, event: Event) -> Option<(Arc<dyn Node>, Dispatch)> {
+        self.handle.set_now(self.now());
+        if let Event::Timer { timer, .. } = &event {
+            if self.cancelled_timers.remove(timer) {
+                return None;
+            }
+        }
+        self.stats.events_processed += 1;
+        if !self.invariants.is_empty() {
+            self.current = Some(Candidate::new(seq, self.now(), &event));
+        }
+        self.dispatch(seq, event)
+    }
+
+    /// Hands a dispatched event to its node, with the network lock released
+    async fn deliver(network: &Arc<Mutex<Network>>, dispatch: Option<(Arc<dyn Node>, Dispatch)>) {
+        match dispatch {
+            Some((node, Dispatch::Run)) => {
+                node.run().await;
+            }
+            Some((node, Dispatch::Recover)) => {
+                node.recover().await;
+            }
+            Some((node, Dispatch::Receive { envelope })) => {
+                node.receive(envelope).await;
+            }
+            Some((node, Dispatch::Timer { tag })) => {
+                node.on_timer(tag).await;
+            }
+            Some((node, Dispatch::Handle { envelope })) => {
+                node.handle(envelope).await;
+                let mut network = network.lock().await;
+                network.flush().await;
+                network.finish_handling(node.ident());
+            }
+            None => {}
+        }
+    }
+
+    /// Processes events until none are pending, returning the final virtual time
+    pub async fn run(network: &Arc<Mutex<Network>>) -> Time {
+        while Self::step(network).await {}
+        network.lock().await.now()
+    }
+
+    /// Processes all events scheduled up to and including `until`, then advances
+    /// the clock to `until`. Returns the final virtual time.
+    pub async fn run_until(network: &Arc<Mutex<Network>>, until: Time) -> Time {
+        loop {
+            let next = {
+                let mut network = network.lock().await;
+                network.flush().await;
+                network.scheduler.peek_time()
+            };
+            match next {
+                Some(time) if time <= until => {
+                    if !Self::step(network).await {
+                        break;
+                    }
+                }
+                _ => break,
+            }
+        }
+        let mut network = network.lock().await;
+        network.scheduler.advance_to(until);
+        network.handle.set_now(network.now());
+        network.now()
+    }
+
+    /// Processes events until the network is quiescent or one of `limits` is
+    /// reached, and summarizes the run
+    pub async fn run_to_completion(network: &Arc<Mutex<Network>>, limits: RunLimits) -> RunSummary {
+        let events_before = network.lock().await.stats.events_processed;
+        let stop_reason = loop {
+            {
+                let mut network = network.lock().await;
+                network.flush().await;
+                if network.violation.is_some() {
+                    break StopReason::InvariantViolated;
+                }
+                if network.is_quiescent() {
+                    break StopReason::Quiescent;
+                }
+                let events = network.stats.events_processed - events_before;
+                if limits.max_events.is_some_and(|max_events| events >= max_events) {
+                    break StopReason::MaxEvents;
+                }
+                if let (Some(max_time), Some(next)) = (limits.max_time, network.scheduler.peek_time()) {
+                    if next > max_time {
+                        network.scheduler.advance_to(max_time);
+                        network.handle.set_now(network.now());
+                        break StopReason::MaxTime;
+                    }
+                }
+            }
+            Self::step(network).await;
+        };
+
+        let network = network.lock().await;
+        RunSummary {
+            stop_reason,
+            final_time: network.now(),
+            events_processed: network.stats.events_processed - events_before,
+            messages_sent: network.stats.messages_sent,
+            messages_delivered: network.stats.messages_delivered,
+            messages_dropped: network.stats.messages_dropped,
+            bytes_sent: network.stats.bytes_sent,
         }
     }
 }
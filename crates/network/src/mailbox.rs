@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+
+use message::Envelope;
+
+/// Messages delivered to a sequential node that it has not handled yet.
+///
+/// The node is busy from the moment a message reaches an empty mailbox until
+/// the last queued message has been handled.
+#[derive(Debug, Default)]
+pub(crate) struct Mailbox {
+    messages: VecDeque<Envelope>,
+    busy: bool,
+}
+
+impl Mailbox {
+    /// Queues a message, returning true if the node was idle and must now be
+    /// scheduled to process it
+    pub(crate) fn push(&mut self, envelope: Envelope) -> bool {
+        self.messages.push_back(envelope);
+        !std::mem::replace(&mut self.busy, true)
+    }
+
+    /// Returns the next message to handle without taking it
+    pub(crate) fn peek(&self) -> Option<&Envelope> {
+        self.messages.front()
+    }
+
+    /// Takes the next message to handle
+    pub(crate) fn pop(&mut self) -> Option<Envelope> {
+        self.messages.pop_front()
+    }
+
+    /// Called when the node finished handling a message. Returns true if more
+    /// messages are queued; otherwise the node becomes idle.
+    pub(crate) fn finish(&mut self) -> bool {
+        self.busy = !self.messages.is_empty();
+        self.busy
+    }
+
+    /// Returns the queued messages in handling order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Envelope> {
+        self.messages.iter()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub(crate) fn is_busy(&self) -> bool {
+        self.busy
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use crate::Time;
+
+/// What happens to a message that would cross a partition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrossingPolicy {
+    /// The message is lost
+    Drop,
+    /// The message is held and delivered when the partition heals
+    Hold,
+}
+
+/// A split of the nodes into groups that cannot reach each other.
+/// Nodes not listed in any group can still reach everyone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partition {
+    groups: Vec<BTreeSet<i32>>,
+}
+
+impl Partition {
+    /// Returns an error if a node is listed in more than one group
+    pub fn new(groups: Vec<Vec<i32>>) -> Result<Self, String> {
+        let mut listed = BTreeSet::new();
+        for ident in groups
+            .iter()
+            .flat_map(|group| group.iter().collect::<BTreeSet<_>>())
+        {
+            if !listed.insert(*ident) {
+                return Err(format!("node {} is in more than one group", ident));
+            }
+        }
+        Ok(Partition {
+            groups: groups
+                .into_iter()
+                .map(|group| group.into_iter().collect())
+                .collect(),
+        })
+    }
+
+    fn group_of(&self, ident: i32) -> Option<usize> {
+        self.groups.iter().position(|group| group.contains(&ident))
+    }
+
+    /// Returns true if the partition prevents messages between `a` and `b`
+    pub fn separates(&self, a: i32, b: i32) -> bool {
+        match (self.group_of(a), self.group_of(b)) {
+            (Some(group_a), Some(group_b)) => group_a != group_b,
+            _ => false,
+        }
+    }
+}
+
+/// A scripted change to the partitioning of the network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartitionAction {
+    Split(Partition, CrossingPolicy),
+    Heal,
+}
+
+/// Partition actions at absolute virtual times, parsed from scripts like
+/// `"t=10 split {0..3}|{4..9} hold; t=50 heal"`.
+///
+/// Groups are separated by `|` and list nodes or inclusive ranges of nodes,
+/// e.g. `{0..2, 5}`; a node may only be in one group. A split may end with
+/// `drop` (the default) or `hold` to choose what happens to messages
+/// crossing it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartitionSchedule {
+    actions: Vec<(Time, PartitionAction)>,
+}
+
+impl PartitionSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an action at virtual time `at`
+    pub fn at(mut self, at: Time, action: PartitionAction) -> Self {
+        self.actions.push((at, action));
+        self
+    }
+
+    /// Returns the scheduled actions in the order they were added
+    pub fn actions(&self) -> &[(Time, PartitionAction)] {
+        &self.actions
+    }
+}
+
+fn parse_ident(s: &str) -> Result<i32, String> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("invalid node ident: {:?}", s))
+}
+
+fn parse_group(s: &str) -> Result<Vec<i32>, String> {
+    let inner = s
+        .trim()
+        .strip_prefix('{')
+        .and_then(|s| s.strip_suffix('}'))
+        .ok_or_else(|| format!("group must be enclosed in braces: {:?}", s))?;
+    let mut group = Vec::new();
+    for item in inner.split(',').filter(|item| !item.trim().is_empty()) {
+        match item.split_once("..") {
+            Some((start, end)) => {
+                let (start, end) = (parse_ident(start)?, parse_ident(end)?);
+                if start > end {
+                    return Err(format!("reversed range: {:?}", item.trim()));
+                }
+                group.extend(start..=end);
+            }
+            None => group.push(parse_ident(item)?),
+        }
+    }
+    Ok(group)
+}
+
+/// Splits off the first word of `s`, returning it and the rest
+fn first_word(s: &str) -> (&str, &str) {
+    let s = s.trim();
+    s.split_once(char::is_whitespace).unwrap_or((s, ""))
+}
+
+fn parse_action(s: &str) -> Result<(Time, PartitionAction), String> {
+    let (at, rest) = first_word(s);
+    let at = at
+        .strip_prefix("t=")
+        .ok_or_else(|| format!("action must start with t=<time>: {:?}", s))?;
+    let at: Time = at.parse().map_err(|_| format!("invalid time: {:?}", at))?;
+
+    let (verb, rest) = first_word(rest);
+    let rest = rest.trim();
+    let action = match verb {
+        "heal" if rest.is_empty() => PartitionAction::Heal,
+        "heal" => return Err(format!("unexpected {:?} in {:?}", rest, s)),
+        "split" => {
+            // Groups may contain spaces; the policy follows the last group
+            let end = rest.rfind('}').map_or(0, |index| index + 1);
+            let (groups, policy) = rest.split_at(end);
+            if groups.is_empty() {
+                return Err(format!("split without groups: {:?}", s));
+            }
+            let groups = groups
+                .split('|')
+                .map(parse_group)
+                .collect::<Result<Vec<_>, _>>()?;
+            let policy = match policy.trim() {
+                "" | "drop" => CrossingPolicy::Drop,
+                "hold" => CrossingPolicy::Hold,
+                other => return Err(format!("unknown crossing policy: {:?}", other)),
+            };
+            PartitionAction::Split(Partition::new(groups)?, policy)
+        }
+        other => return Err(format!("unknown partition action: {:?}", other)),
+    };
+    Ok((at, action))
+}
+
+impl FromStr for PartitionSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let actions = s
+            .split(';')
+            .filter(|action| !action.trim().is_empty())
+            .map(parse_action)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PartitionSchedule { actions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_separates() {
+        let partition = Partition::new(vec![vec![0, 1], vec![2, 3]]).unwrap();
+        assert!(!partition.separates(0, 1));
+        assert!(partition.separates(1, 2));
+        // Unlisted nodes are not cut off
+        assert!(!partition.separates(0, 4));
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let schedule: PartitionSchedule =
+            "t=10 split {0..3}|{4..9}; t=20 split { 0, 2 } | {1..1} hold; t=50 heal"
+                .parse()
+                .unwrap();
+        let expected = PartitionSchedule::new()
+            .at(
+                10,
+                PartitionAction::Split(
+                    Partition::new(vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7, 8, 9]]).unwrap(),
+                    CrossingPolicy::Drop,
+                ),
+            )
+            .at(
+                20,
+                PartitionAction::Split(
+                    Partition::new(vec![vec![0, 2], vec![1]]).unwrap(),
+                    CrossingPolicy::Hold,
+                ),
+            )
+            .at(50, PartitionAction::Heal);
+        assert_eq!(schedule, expected);
+
+        assert!("t=10 split 0..3".parse::<PartitionSchedule>().is_err());
+        assert!("split {0}|{1}".parse::<PartitionSchedule>().is_err());
+        assert!("t=5 heal now".parse::<PartitionSchedule>().is_err());
+        assert!("t=5 split {0}|{1} hold now"
+            .parse::<PartitionSchedule>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_groups() {
+        let reversed = "t=10 split {5..2}|{3}".parse::<PartitionSchedule>();
+        assert_eq!(reversed, Err("reversed range: \"5..2\"".to_string()));
+        let overlapping = "t=10 split {0..3}|{3, 4}".parse::<PartitionSchedule>();
+        assert_eq!(
+            overlapping,
+            Err("node 3 is in more than one group".to_string())
+        );
+        // Repeating a node within its own group is harmless
+        assert!(Partition::new(vec![vec![1, 1], vec![2]]).is_ok());
+    }
+}
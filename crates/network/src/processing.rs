@@ -0,0 +1,49 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+use message::Message;
+
+use crate::Time;
+
+/// Virtual processing time per message kind, for nodes to declare what
+/// handling each kind of message costs them
+#[derive(Debug, Clone, Default)]
+pub struct ProcessingCosts {
+    default: Time,
+    costs: HashMap<TypeId, Time>,
+}
+
+impl ProcessingCosts {
+    /// Creates a table where every message kind costs `default`
+    pub fn new(default: Time) -> Self {
+        ProcessingCosts {
+            default,
+            costs: HashMap::new(),
+        }
+    }
+
+    /// Sets the cost of handling messages of type `M`
+    pub fn with<M: Message>(mut self, cost: Time) -> Self {
+        self.costs.insert(TypeId::of::<M>(), cost);
+        self
+    }
+
+    /// Returns the cost of handling `message`
+    pub fn cost(&self, message: &dyn Message) -> Time {
+        let kind = Any::type_id(message.as_any_ref());
+        self.costs.get(&kind).copied().unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use message::MessageString;
+
+    #[test]
+    fn test_costs_by_kind() {
+        let costs = ProcessingCosts::new(1).with::<MessageString>(5);
+        assert_eq!(costs.cost(&MessageString::new("block".to_string())), 5);
+        assert_eq!(costs.cost(&42i32), 1);
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use message::Envelope;
+
+use crate::{Node, Time, TimerId};
+
+/// Timer tags at or above this value belong to RPC clients; nodes using
+/// `RpcClient` keep their own timer tags below it
+pub const RPC_TAG: u64 = 1 << 62;
+
+/// An RPC timer tag holds the call id in its low bits and the index of the
+/// client that made the call above them
+const CALL_BITS: u32 = 40;
+
+/// A request, tagged with the index of the client that made it and the id
+/// that correlates it with its response
+#[derive(Debug, Clone)]
+pub struct RpcRequest<Q> {
+    pub client: u16,
+    pub call: u64,
+    pub request: Q,
+}
+
+/// The response to the request with the same client index and call id
+#[derive(Debug, Clone)]
+pub struct RpcResponse<R> {
+    pub client: u16,
+    pub call: u64,
+    pub response: R,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcError {
+    /// No response arrived in time, after all retries
+    TimedOut,
+}
+
+/// How a call ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcReply<R> {
+    /// Id returned by `RpcClient::call`
+    pub call: u64,
+    pub target: i32,
+    /// Number of times the request was sent
+    pub attempts: u32,
+    pub result: Result<R, RpcError>,
+}
+
+struct Pending<Q> {
+    target: i32,
+    request: Q,
+    attempts: u32,
+    timer: TimerId,
+}
+
+struct ClientState<Q> {
+    next_call: u64,
+    pending: HashMap<u64, Pending<Q>>,
+}
+
+/// Calls to other nodes that expect a response of type `R` to a request of
+/// type `Q`, for nodes to embed.
+///
+/// Each call waits up to `timeout` for its response and resends the request
+/// up to `retries` times before giving up. The node passes its envelopes to
+/// `receive_response` and its timers to `on_timer`, which hand back the
+/// replies of finished calls. Peers answer with `serve`. A node with
+/// several clients gives each a distinct index with `client`, so that they
+/// tell their responses and timers apart.
+pub struct RpcClient<Q, R> {
+    timeout: Time,
+    retries: u32,
+    client: u16,
+    state: Mutex<ClientState<Q>>,
+    response: PhantomData<fn() -> R>,
+}
+
+impl<Q, R> RpcClient<Q, R>
+where
+    Q: std::fmt::Debug + Clone + Send + Sync + 'static,
+    R: std::fmt::Debug + Clone + Send + Sync + 'static,
+{
+    /// Calls time out after `timeout` without retries
+    pub fn new(timeout: Time) -> Self {
+        RpcClient {
+            timeout,
+            retries: 0,
+            client: 0,
+            state: Mutex::new(ClientState {
+                next_call: 0,
+                pending: HashMap::new(),
+            }),
+            response: PhantomData,
+        }
+    }
+
+    /// Resends a request up to `retries` times when it times out
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets the index of this client among the clients of its node
+    pub fn client(mut self, client: u16) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Returns the tag of the timer of a call
+    fn timer_tag(&self, call: u64) -> u64 {
+        RPC_TAG | (u64::from(self.client) << CALL_BITS) | call
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ClientState<Q>> {
+        self.state.lock().expect("rpc client poisoned")
+    }
+
+    /// Returns the number of calls waiting for a response
+    pub fn pending(&self) -> usize {
+        self.state().pending.len()
+    }
+
+    /// Sends `request` to `target` from `node` and returns the call id its
+    /// reply will carry
+    pub async fn call<N: Node + ?Sized>(&self, node: &N, target: i32, request: Q) -> u64 {
+        let call = {
+            let mut state = self.state();
+            state.next_call += 1;
+            state.next_call - 1
+        };
+        assert!(
+            call < 1 << CALL_BITS,
+            "rpc client {} ran out of call ids",
+            self.client
+        );
+        let timer = self.send(node, target, call, request.clone());
+        self.state().pending.insert(
+            call,
+            Pending {
+                target,
+                request,
+                attempts: 1,
+                timer,
+            },
+        );
+        call
+    }
+
+    /// Sends the request of a call and starts its timeout
+    fn send<N: Node + ?Sized>(&self, node: &N, target: i32, call: u64, request: Q) -> TimerId {
+        let network = node.network();
+        let message = RpcRequest {
+            client: self.client,
+            call,
+            request,
+        };
+        network.send(node.ident(), target, Box::new(message), None);
+        network.set_timer(node.ident(), self.timeout, self.timer_tag(call))
+    }
+
+    /// Handles a message received by `node`. Returns the reply if it is the
+    /// response to a pending call, None for late or duplicate responses, and
+    /// the envelope back if it is not a response to this client, including
+    /// responses from a node other than the one called.
+    pub async fn receive_response<N: Node + ?Sized>(
+        &self,
+        node: &N,
+        envelope: Envelope,
+    ) -> Result<Option<RpcReply<R>>, Envelope> {
+        let response = envelope.downcast_ref::<RpcResponse<R>>().cloned();
+        let Some(RpcResponse {
+            client,
+            call,
+            response,
+        }) = response
+        else {
+            return Err(envelope);
+        };
+        if client != self.client {
+            return Err(envelope);
+        }
+        let pending = {
+            let mut state = self.state();
+            match state.pending.get(&call) {
+                Some(pending) if pending.target != envelope.sender => return Err(envelope),
+                Some(_) => state.pending.remove(&call),
+                None => None,
+            }
+        };
+        let Some(pending) = pending else {
+            return Ok(None);
+        };
+        node.cancel_timer(pending.timer).await;
+        Ok(Some(RpcReply {
+            call,
+            target: pending.target,
+            attempts: pending.attempts,
+            result: Ok(response),
+        }))
+    }
+
+    /// Handles a timer of `node`. Resends the request of a call that timed
+    /// out while it has retries left, and returns its reply once it has
+    /// none. Returns None for other timers.
+    pub async fn on_timer<N: Node + ?Sized>(&self, node: &N, tag: u64) -> Option<RpcReply<R>> {
+        if tag < RPC_TAG || (tag & !RPC_TAG) >> CALL_BITS != u64::from(self.client) {
+            return None;
+        }
+        let call = tag & ((1 << CALL_BITS) - 1);
+        let retry = {
+            let mut state = self.state();
+            let pending = state.pending.get_mut(&call)?;
+            if pending.attempts > self.retries {
+                None
+            } else {
+                pending.attempts += 1;
+                Some((pending.target, pending.request.clone()))
+            }
+        };
+        match retry {
+            Some((target, request)) => {
+                node.log("retry", &format!("call {} to {:2}", call, target))
+                    .await;
+                let timer = self.send(node, target, call, request);
+                if let Some(pending) = self.state().pending.get_mut(&call) {
+                    pending.timer = timer;
+                }
+                None
+            }
+            None => {
+                let pending = self.state().pending.remove(&call)?;
+                node.log("timeout", &format!("call {} to {:2}", call, pending.target))
+                    .await;
+                Some(RpcReply {
+                    call,
+                    target: pending.target,
+                    attempts: pending.attempts,
+                    result: Err(RpcError::TimedOut),
+                })
+            }
+        }
+    }
+}
+
+impl<Q, R> std::fmt::Debug for RpcClient<Q, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "RpcClient({}, timeout {}, {} retries)",
+            self.client, self.timeout, self.retries
+        )
+    }
+}
+
+/// Answers a request of type `Q` received by `node` with what `handler`
+/// returns for its sender and request; a handler returning None leaves the
+/// request unanswered. Returns the envelope back if it is not such a request.
+pub async fn serve<Q, R, N, F>(node: &N, envelope: Envelope, handler: F) -> Result<(), Envelope>
+where
+    Q: std::fmt::Debug + Clone + Send + Sync + 'static,
+    R: std::fmt::Debug + Clone + Send + Sync + 'static,
+    N: Node + ?Sized,
+    F: FnOnce(i32, &Q) -> Option<R>,
+{
+    let request = envelope.downcast_ref::<RpcRequest<Q>>().cloned();
+    let Some(RpcRequest {
+        client,
+        call,
+        request,
+    }) = request
+    else {
+        return Err(envelope);
+    };
+    if let Some(response) = handler(envelope.sender, &request) {
+        let message = RpcResponse {
+            client,
+            call,
+            response,
+        };
+        node.send(envelope.sender, Box::new(message), None).await;
+    }
+    Ok(())
+}
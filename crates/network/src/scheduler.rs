@@ -0,0 +1,269 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use message::Envelope;
+
+use crate::crash::Restart;
+use crate::partition::PartitionAction;
+
+/// Virtual simulation time, in abstract time units
+pub type Time = u64;
+
+/// Identifies a pending timer so that it can be cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerId(pub u64);
+
+/// An event waiting in the scheduler queue
+#[derive(Debug)]
+pub enum Event {
+    /// Runs the process of a node
+    Start { ident: i32 },
+    /// Delivers a message to its target
+    Deliver {
+        target: i32,
+        envelope: Envelope,
+        delay: Time,
+    },
+    /// Hands the next message in a sequential node's mailbox to the node
+    Process { ident: i32 },
+    /// Hands a message to a node that has finished spending its processing
+    /// time on it
+    Processed { ident: i32, envelope: Envelope },
+    /// Fires a timer set by a node, unless it has been cancelled
+    Timer {
+        ident: i32,
+        tag: u64,
+        timer: TimerId,
+    },
+    /// Changes the partitioning of the network
+    Partition(PartitionAction),
+    /// Crashes a node
+    Crash { ident: i32 },
+    /// Restarts a crashed node
+    Recover { ident: i32, restart: Restart },
+}
+
+/// An event together with the key it is ordered by
+#[derive(Debug)]
+struct Scheduled {
+    time: Time,
+    tiebreak: u64,
+    seq: u64,
+    event: Event,
+}
+
+impl Scheduled {
+    fn key(&self) -> (Time, u64, u64) {
+        (self.time, self.tiebreak, self.seq)
+    }
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    // Reversed so that the max-heap pops the earliest event first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
+
+/// Discrete-event scheduler: a virtual clock and a queue of pending events
+/// keyed by (virtual time, tie-breaker, sequence number).
+///
+/// The tie-breaker orders events due at the same time; callers draw it from
+/// the simulation RNG so that simultaneous events interleave per seed.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    now: Time,
+    next_seq: u64,
+    queue: BinaryHeap<Scheduled>,
+}
+
+impl Scheduler {
+    /// Creates a scheduler with the clock at zero and no pending events
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the current virtual time
+    pub fn now(&self) -> Time {
+        self.now
+    }
+
+    /// Returns the number of pending events
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns true if no events are pending
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Returns the time of the next pending event
+    pub fn peek_time(&self) -> Option<Time> {
+        self.queue.peek().map(|scheduled| scheduled.time)
+    }
+
+    /// Schedules an event `delay` time units from now and returns its sequence
+    /// number. Events that would fall beyond the end of time are due at its end.
+    pub fn schedule(&mut self, delay: Time, tiebreak: u64, event: Event) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.queue.push(Scheduled {
+            time: self.now.saturating_add(delay),
+            tiebreak,
+            seq,
+            event,
+        });
+        seq
+    }
+
+    /// Removes the next pending event, advances the clock to its time and
+    /// returns it with its sequence number
+    pub fn pop(&mut self) -> Option<(u64, Event)> {
+        let scheduled = self.queue.pop()?;
+        self.now = scheduled.time;
+        Some((scheduled.seq, scheduled.event))
+    }
+
+    /// Returns the sequence numbers, times and events of all pending events,
+    /// in the order `pop` would return them
+    pub fn pending(&self) -> Vec<(u64, Time, &Event)> {
+        let mut pending: Vec<&Scheduled> = self.queue.iter().collect();
+        pending.sort_by_key(|scheduled| scheduled.key());
+        pending
+            .into_iter()
+            .map(|scheduled| (scheduled.seq, scheduled.time, &scheduled.event))
+            .collect()
+    }
+
+    /// Returns the sequence numbers and events of all pending events due at
+    /// the earliest pending time, in the order `pop` would return them
+    pub fn due(&self) -> Vec<(u64, &Event)> {
+        let time = self.peek_time();
+        self.pending()
+            .into_iter()
+            .take_while(|(_, at, _)| Some(*at) == time)
+            .map(|(seq, _, event)| (seq, event))
+            .collect()
+    }
+
+    /// Removes the pending event with sequence number `seq`, regardless of its
+    /// place in the queue. The clock advances to the event's time but never
+    /// moves backwards.
+    pub fn pop_seq(&mut self, seq: u64) -> Option<Event> {
+        let mut pending = std::mem::take(&mut self.queue).into_vec();
+        let found = pending
+            .iter()
+            .position(|scheduled| scheduled.seq == seq)
+            .map(|index| pending.swap_remove(index));
+        self.queue = pending.into();
+        let scheduled = found?;
+        self.now = self.now.max(scheduled.time);
+        Some(scheduled.event)
+    }
+
+    /// Removes all pending events matching `remove` and returns them with
+    /// their sequence numbers, in the order `pop` would have returned them
+    pub fn remove_where(&mut self, mut remove: impl FnMut(&Event) -> bool) -> Vec<(u64, Event)> {
+        let (mut removed, kept): (Vec<Scheduled>, Vec<Scheduled>) = std::mem::take(&mut self.queue)
+            .into_vec()
+            .into_iter()
+            .partition(|scheduled| remove(&scheduled.event));
+        self.queue = kept.into();
+        removed.sort_by_key(|scheduled| scheduled.key());
+        removed
+            .into_iter()
+            .map(|scheduled| (scheduled.seq, scheduled.event))
+            .collect()
+    }
+
+    /// Advances the clock to `time` without processing events; never moves backwards
+    pub fn advance_to(&mut self, time: Time) {
+        self.now = self.now.max(time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(ident: i32) -> Event {
+        Event::Start { ident }
+    }
+
+    fn ident(event: &Event) -> i32 {
+        match event {
+            Event::Start { ident } => *ident,
+            Event::Deliver { target, .. } => *target,
+            Event::Timer { ident, .. }
+            | Event::Process { ident }
+            | Event::Processed { ident, .. }
+            | Event::Crash { ident }
+            | Event::Recover { ident, .. } => *ident,
+            Event::Partition(_) => -1,
+        }
+    }
+
+    #[test]
+    fn test_pops_in_time_tiebreak_then_sequence_order() {
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule(5, 0, start(0));
+        scheduler.schedule(2, 0, start(1));
+        scheduler.schedule(5, 0, start(2));
+        scheduler.schedule(0, 0, start(3));
+        scheduler.schedule(5, 1, start(4));
+        scheduler.schedule(5, 0, start(5));
+
+        let mut order: Vec<(Time, i32)> = Vec::new();
+        while let Some((_, event)) = scheduler.pop() {
+            order.push((scheduler.now(), ident(&event)));
+        }
+        assert_eq!(order, vec![(0, 3), (2, 1), (5, 0), (5, 2), (5, 5), (5, 4)]);
+        assert_eq!(scheduler.now(), 5);
+    }
+
+    #[test]
+    fn test_due_events_can_be_taken_out_of_order() {
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule(2, 0, start(0));
+        let second = scheduler.schedule(2, 1, start(1));
+        scheduler.schedule(3, 0, start(2));
+
+        let due: Vec<u64> = scheduler.due().into_iter().map(|(seq, _)| seq).collect();
+        assert_eq!(due, vec![0, second]);
+        assert_eq!(scheduler.pop_seq(second).map(|event| ident(&event)), Some(1));
+        assert_eq!(scheduler.now(), 2);
+        assert_eq!(scheduler.pop().map(|(seq, _)| seq), Some(0));
+    }
+
+    #[test]
+    fn test_delays_are_relative_to_now() {
+        let mut scheduler = Scheduler::new();
+        scheduler.advance_to(10);
+        scheduler.schedule(3, 0, start(0));
+        assert_eq!(scheduler.peek_time(), Some(13));
+
+        // The clock never moves backwards
+        scheduler.advance_to(4);
+        assert_eq!(scheduler.now(), 10);
+
+        // Delays beyond the end of time saturate
+        scheduler.schedule(Time::MAX, 0, start(1));
+        assert_eq!(scheduler.pop().map(|_| scheduler.now()), Some(13));
+        assert_eq!(scheduler.peek_time(), Some(Time::MAX));
+    }
+}
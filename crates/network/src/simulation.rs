@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use logging::Logger;
+
+use crate::{LatencyModel, Network, Node, RunLimits, RunSummary, SchedulingStrategy, Time};
+
+/// A reproducible simulation run.
+///
+/// The simulation is constructed from a seed and owns the network, whose RNG
+/// breaks ties between simultaneous events and draws random delays; nodes
+/// draw their choices from per-node streams derived from the same seed. Two
+/// simulations built from the same seed and driven the same way produce
+/// identical logs.
+pub struct Simulation {
+    seed: u64,
+    network: Arc<Mutex<Network>>,
+}
+
+impl Simulation {
+    /// Creates a simulation with an empty network
+    pub fn new(seed: u64, latency: Box<dyn LatencyModel>, logger: Box<dyn Logger>) -> Self {
+        Simulation {
+            seed,
+            network: Network::new(seed, None, latency, logger),
+        }
+    }
+
+    /// Returns the seed this simulation was constructed from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns a reference to the network
+    pub fn network(&self) -> Arc<Mutex<Network>> {
+        self.network.clone()
+    }
+
+    /// Adds a new node to the network
+    pub async fn add_node(&self, node: Arc<dyn Node>) {
+        self.network.lock().await.add_node(node);
+    }
+
+    /// Schedules all nodes to start at the current virtual time
+    pub async fn start_all_nodes(&self) {
+        self.network.lock().await.start_all_nodes().await;
+    }
+
+    /// Lets a strategy choose the order of simultaneous events
+    pub async fn set_strategy(&self, strategy: Box<dyn SchedulingStrategy>) {
+        self.network.lock().await.set_strategy(strategy);
+    }
+
+    /// Registers an invariant that must hold after every processed event
+    pub async fn add_invariant<F>(&self, name: &str, check: F)
+    where
+        F: Fn(&Network) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.network.lock().await.add_invariant(name, check);
+    }
+
+    /// Returns the current virtual time
+    pub async fn now(&self) -> Time {
+        self.network.lock().await.now()
+    }
+
+    /// Processes events until none are pending, returning the final virtual time
+    pub async fn run(&self) -> Time {
+        Network::run(&self.network).await
+    }
+
+    /// Processes all events scheduled up to and including `until`
+    pub async fn run_until(&self, until: Time) -> Time {
+        Network::run_until(&self.network, until).await
+    }
+
+    /// Processes events until the network is quiescent or a limit is reached
+    pub async fn run_to_completion(&self, limits: RunLimits) -> RunSummary {
+        Network::run_to_completion(&self.network, limits).await
+    }
+}
@@ -0,0 +1,69 @@
+use crate::Time;
+
+/// Counters kept by the network since it was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkStats {
+    /// Events taken off the scheduler queue, not counting cancelled timers
+    pub events_processed: u64,
+    /// Messages handed to the network, one per target of a broadcast
+    pub messages_sent: u64,
+    /// Messages that reached their target
+    pub messages_delivered: u64,
+    /// Messages lost to injected faults or partitions
+    pub messages_dropped: u64,
+    /// Total size of the messages sent, see `Network::message_size`. Only
+    /// counted while a link is limited or message sizes are set.
+    pub bytes_sent: u64,
+}
+
+/// Guards that stop a run of a protocol that never goes quiet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunLimits {
+    /// Stop before processing events scheduled after this virtual time
+    pub max_time: Option<Time>,
+    /// Stop after processing this many events in the run
+    pub max_events: Option<u64>,
+}
+
+impl RunLimits {
+    /// No limits: run until the network is quiescent
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_time(mut self, max_time: Time) -> Self {
+        self.max_time = Some(max_time);
+        self
+    }
+
+    pub fn max_events(mut self, max_events: u64) -> Self {
+        self.max_events = Some(max_events);
+        self
+    }
+}
+
+/// Why a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// No events were pending and no node was busy
+    Quiescent,
+    /// The next event was scheduled after the time limit
+    MaxTime,
+    /// The event limit was reached
+    MaxEvents,
+    /// An invariant failed; see `Network::violation`
+    InvariantViolated,
+}
+
+/// Summary of a run. Message counts are totals since the network was created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunSummary {
+    pub stop_reason: StopReason,
+    pub final_time: Time,
+    /// Events processed during this run
+    pub events_processed: u64,
+    pub messages_sent: u64,
+    pub messages_delivered: u64,
+    pub messages_dropped: u64,
+    pub bytes_sent: u64,
+}
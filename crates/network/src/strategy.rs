@@ -0,0 +1,154 @@
+use utils::SimRng;
+
+use crate::partition::PartitionAction;
+use crate::{Event, Time};
+
+/// A pending event that could be processed next
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    /// Sequence number of the event in the scheduler
+    pub seq: u64,
+    pub time: Time,
+    /// Sender of the message, or the node itself for other node events.
+    /// Network events use -1.
+    pub sender: i32,
+    /// Node the event happens at, or -1 for network events
+    pub target: i32,
+    /// Debug form of the message, or a description of the event
+    pub description: String,
+}
+
+impl Candidate {
+    pub(crate) fn new(seq: u64, time: Time, event: &Event) -> Self {
+        let (sender, target, description) = match event {
+            Event::Start { ident } => (*ident, *ident, "start".to_string()),
+            Event::Deliver {
+                target, envelope, ..
+            } => (envelope.sender, *target, format!("{:?}", envelope.message)),
+            Event::Process { ident } => (*ident, *ident, "process mailbox".to_string()),
+            Event::Processed { ident, envelope } => {
+                (envelope.sender, *ident, format!("{:?}", envelope.message))
+            }
+            Event::Timer { ident, tag, .. } => (*ident, *ident, format!("timer tag {}", tag)),
+            Event::Partition(PartitionAction::Split(partition, policy)) => {
+                (-1, -1, format!("split {:?} ({:?})", partition, policy))
+            }
+            Event::Partition(PartitionAction::Heal) => (-1, -1, "heal".to_string()),
+            Event::Crash { ident } => (*ident, *ident, "crash".to_string()),
+            Event::Recover { ident, restart } => {
+                (*ident, *ident, format!("recover ({:?})", restart))
+            }
+        };
+        Candidate {
+            seq,
+            time,
+            sender,
+            target,
+            description,
+        }
+    }
+}
+
+/// Decides which of several simultaneous events the network processes next.
+///
+/// Without a strategy the network processes simultaneous events in the order
+/// of their tie-breakers, which are drawn from the simulation RNG. A strategy
+/// takes over that choice so that a test can steer the interleaving.
+pub trait SchedulingStrategy: Send + Sync {
+    /// Returns the index into `candidates` of the event to process next.
+    /// `candidates` holds at least two events, in default order.
+    fn choose(&mut self, candidates: &[Candidate], rng: &mut SimRng) -> usize;
+}
+
+/// Picks uniformly among the candidates using the simulation RNG
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomStrategy;
+
+impl SchedulingStrategy for RandomStrategy {
+    fn choose(&mut self, candidates: &[Candidate], rng: &mut SimRng) -> usize {
+        rng.gen_range(0..candidates.len() as u64) as usize
+    }
+}
+
+/// Takes turns between nodes: picks the candidate at the lowest node ident
+/// above the one picked last, wrapping around to the lowest ident
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RoundRobinStrategy {
+    last: Option<i32>,
+}
+
+impl RoundRobinStrategy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SchedulingStrategy for RoundRobinStrategy {
+    fn choose(&mut self, candidates: &[Candidate], _rng: &mut SimRng) -> usize {
+        let lowest = |filter: &dyn Fn(i32) -> bool| {
+            candidates
+                .iter()
+                .enumerate()
+                .filter(|(_, candidate)| filter(candidate.target))
+                .min_by_key(|(_, candidate)| candidate.target)
+                .map(|(index, _)| index)
+        };
+        let index = match self.last {
+            Some(last) => lowest(&|target| target > last),
+            None => None,
+        }
+        .or_else(|| lowest(&|_| true))
+        .unwrap_or(0);
+        self.last = Some(candidates[index].target);
+        index
+    }
+}
+
+/// Delegates the choice to a closure
+pub struct CallbackStrategy<F> {
+    callback: F,
+}
+
+impl<F> CallbackStrategy<F>
+where
+    F: FnMut(&[Candidate]) -> usize + Send + Sync,
+{
+    pub fn new(callback: F) -> Self {
+        CallbackStrategy { callback }
+    }
+}
+
+impl<F> SchedulingStrategy for CallbackStrategy<F>
+where
+    F: FnMut(&[Candidate]) -> usize + Send + Sync,
+{
+    fn choose(&mut self, candidates: &[Candidate], _rng: &mut SimRng) -> usize {
+        (self.callback)(candidates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(seq: u64, target: i32) -> Candidate {
+        Candidate {
+            seq,
+            time: 0,
+            sender: target,
+            target,
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_round_robin_takes_turns() {
+        let mut rng = SimRng::new(0);
+        let mut strategy = RoundRobinStrategy::new();
+        let candidates = [candidate(0, 2), candidate(1, 0), candidate(2, 1)];
+        let picked: Vec<i32> = (0..4)
+            .map(|_| candidates[strategy.choose(&candidates, &mut rng)].target)
+            .collect();
+        assert_eq!(picked, vec![0, 1, 2, 0]);
+    }
+}
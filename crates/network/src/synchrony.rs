@@ -0,0 +1,105 @@
+use message::Message;
+
+use crate::Time;
+
+/// The partial synchrony model: before the global stabilization time (GST)
+/// messages may be delayed arbitrarily, but every message is delivered
+/// within `delta` of leaving its sender or of GST, whichever is later. A
+/// message leaves its sender once its link has transferred it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialSynchrony {
+    pub gst: Time,
+    pub delta: Time,
+}
+
+impl PartialSynchrony {
+    pub fn new(gst: Time, delta: Time) -> Self {
+        PartialSynchrony { gst, delta }
+    }
+
+    /// Returns the longest propagation delay allowed for a message that
+    /// leaves its sender at `now`. A GST of `Time::MAX` never arrives, and
+    /// messages may then be held until the end of time.
+    pub fn max_delay(&self, now: Time) -> Time {
+        now.max(self.gst)
+            .saturating_add(self.delta)
+            .saturating_sub(now)
+    }
+}
+
+/// An adversary that controls when messages in flight are delivered.
+///
+/// Unlike a Byzantine node it cannot change messages, only delay them in
+/// transit; the network caps the delay it chooses at what the synchrony
+/// model allows, and adds it to the time the message takes to leave its
+/// sender.
+pub trait DeliveryAdversary: Send + Sync {
+    /// Chooses the propagation delay of a message sent at virtual time `now`.
+    /// `proposed` is the propagation delay the network would otherwise use
+    /// and `max_delay` the longest one allowed.
+    fn delay(
+        &mut self,
+        now: Time,
+        sender: i32,
+        target: i32,
+        message: &dyn Message,
+        proposed: Time,
+        max_delay: Time,
+    ) -> Time;
+}
+
+impl<F> DeliveryAdversary for F
+where
+    F: FnMut(Time, i32, i32, &dyn Message, Time, Time) -> Time + Send + Sync,
+{
+    fn delay(
+        &mut self,
+        now: Time,
+        sender: i32,
+        target: i32,
+        message: &dyn Message,
+        proposed: Time,
+        max_delay: Time,
+    ) -> Time {
+        self(now, sender, target, message, proposed, max_delay)
+    }
+}
+
+/// Delays every message as long as the model allows
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaxDelay;
+
+impl DeliveryAdversary for MaxDelay {
+    fn delay(
+        &mut self,
+        _now: Time,
+        _sender: i32,
+        _target: i32,
+        _message: &dyn Message,
+        _proposed: Time,
+        max_delay: Time,
+    ) -> Time {
+        max_delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_delay() {
+        let model = PartialSynchrony::new(20, 3);
+        // Before GST, messages may be held until GST + delta
+        assert_eq!(model.max_delay(0), 23);
+        assert_eq!(model.max_delay(19), 4);
+        // Afterwards, delivery takes at most delta
+        assert_eq!(model.max_delay(20), 3);
+        assert_eq!(model.max_delay(50), 3);
+
+        // A GST that never arrives
+        let model = PartialSynchrony::new(Time::MAX, 3);
+        assert_eq!(model.max_delay(0), Time::MAX);
+        assert_eq!(model.max_delay(10), Time::MAX - 10);
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use utils::SimRng;
+
+/// The links between nodes. Links are undirected, and a node can only send
+/// to its neighbors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Topology {
+    links: BTreeMap<i32, BTreeSet<i32>>,
+}
+
+impl Topology {
+    /// A topology of `nodes` nodes without links
+    pub fn new(nodes: usize) -> Self {
+        Topology {
+            links: (0..nodes as i32)
+                .map(|node| (node, BTreeSet::new()))
+                .collect(),
+        }
+    }
+
+    /// Every node linked to every other node
+    pub fn complete(nodes: usize) -> Self {
+        let mut topology = Self::new(nodes);
+        for a in 0..nodes as i32 {
+            for b in a + 1..nodes as i32 {
+                topology.link(a, b);
+            }
+        }
+        topology
+    }
+
+    /// Each node linked to the next one, and the last one to the first
+    pub fn ring(nodes: usize) -> Self {
+        let mut topology = Self::new(nodes);
+        if nodes > 1 {
+            for a in 0..nodes as i32 {
+                topology.link(a, (a + 1) % nodes as i32);
+            }
+        }
+        topology
+    }
+
+    /// A random graph in which every node has `degree` neighbors, not drawn
+    /// uniformly from all such graphs. `nodes * degree` must be even and
+    /// `degree` less than `nodes`.
+    pub fn random_regular(nodes: usize, degree: usize, rng: &mut SimRng) -> Self {
+        assert!(
+            (nodes * degree).is_multiple_of(2) && (degree < nodes || degree == 0),
+            "no {}-regular graph on {} nodes",
+            degree,
+            nodes
+        );
+        // Pair up free link ends at random, starting over when the remaining
+        // ends cannot be paired without self-links or duplicate links
+        'attempt: loop {
+            let mut topology = Self::new(nodes);
+            let mut ends: Vec<i32> = (0..nodes as i32)
+                .flat_map(|node| std::iter::repeat_n(node, degree))
+                .collect();
+            while !ends.is_empty() {
+                let fits = |a: i32, b: i32| a != b && !topology.are_neighbors(a, b);
+                let mut pair = None;
+                for _ in 0..ends.len() {
+                    let i = rng.gen_range(0..ends.len() as u64) as usize;
+                    let j = rng.gen_range(0..ends.len() as u64) as usize;
+                    if fits(ends[i], ends[j]) {
+                        pair = Some((i.min(j), i.max(j)));
+                        break;
+                    }
+                }
+                if pair.is_none() {
+                    let pairs: Vec<(usize, usize)> = (0..ends.len())
+                        .flat_map(|i| (i + 1..ends.len()).map(move |j| (i, j)))
+                        .filter(|(i, j)| fits(ends[*i], ends[*j]))
+                        .collect();
+                    pair = rng.choose(&pairs).copied();
+                }
+                let Some((i, j)) = pair else {
+                    continue 'attempt;
+                };
+                topology.link(ends[i], ends[j]);
+                ends.swap_remove(j);
+                ends.swap_remove(i);
+            }
+            return topology;
+        }
+    }
+
+    /// The Erdős–Rényi graph G(n, p): each possible link is present with
+    /// probability `p`
+    pub fn erdos_renyi(nodes: usize, p: f64, rng: &mut SimRng) -> Self {
+        let mut topology = Self::new(nodes);
+        for a in 0..nodes as i32 {
+            for b in a + 1..nodes as i32 {
+                if rng.gen_bool(p) {
+                    topology.link(a, b);
+                }
+            }
+        }
+        topology
+    }
+
+    /// The Watts–Strogatz small-world graph: a ring in which each node is
+    /// linked to its `neighbors` nearest nodes, half on each side, after
+    /// which each link is rewired to a random node with probability `beta`.
+    /// `neighbors` must be even and less than `nodes`.
+    pub fn small_world(nodes: usize, neighbors: usize, beta: f64, rng: &mut SimRng) -> Self {
+        assert!(
+            neighbors.is_multiple_of(2) && neighbors < nodes,
+            "a small world on {} nodes cannot have {} neighbors per node",
+            nodes,
+            neighbors
+        );
+        let n = nodes as i32;
+        let mut topology = Self::new(nodes);
+        for offset in 1..=(neighbors / 2) as i32 {
+            for a in 0..n {
+                topology.link(a, (a + offset) % n);
+            }
+        }
+        for offset in 1..=(neighbors / 2) as i32 {
+            for a in 0..n {
+                let b = (a + offset) % n;
+                if !rng.gen_bool(beta) {
+                    continue;
+                }
+                let choices: Vec<i32> = (0..n)
+                    .filter(|c| *c != a && !topology.are_neighbors(a, *c))
+                    .collect();
+                if let Some(&c) = rng.choose(&choices) {
+                    topology.unlink(a, b);
+                    topology.link(a, c);
+                }
+            }
+        }
+        topology
+    }
+
+    /// A topology with the given links. Nodes without links are only
+    /// included up to the highest ident that has one.
+    pub fn from_edges(edges: impl IntoIterator<Item = (i32, i32)>) -> Self {
+        let mut topology = Self::default();
+        for (a, b) in edges {
+            topology.link(a, b);
+        }
+        if let Some(&last) = topology.links.keys().next_back() {
+            for node in 0..last {
+                topology.links.entry(node).or_default();
+            }
+        }
+        topology
+    }
+
+    /// Reads an edge list written by `save`
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        std::fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the topology as an edge list
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Adds a link between `a` and `b`
+    pub fn link(&mut self, a: i32, b: i32) {
+        self.links.entry(a).or_default().insert(b);
+        self.links.entry(b).or_default().insert(a);
+    }
+
+    /// Removes the link between `a` and `b`, if any
+    pub fn unlink(&mut self, a: i32, b: i32) {
+        if let Some(links) = self.links.get_mut(&a) {
+            links.remove(&b);
+        }
+        if let Some(links) = self.links.get_mut(&b) {
+            links.remove(&a);
+        }
+    }
+
+    /// Returns the number of nodes
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Returns the links, each once with the lower ident first
+    pub fn edges(&self) -> Vec<(i32, i32)> {
+        self.links
+            .iter()
+            .flat_map(|(a, links)| links.range(a + 1..).map(move |b| (*a, *b)))
+            .collect()
+    }
+
+    /// Returns the neighbors of `node` in ident order
+    pub fn neighbors(&self, node: i32) -> Vec<i32> {
+        self.links
+            .get(&node)
+            .map_or_else(Vec::new, |links| links.iter().copied().collect())
+    }
+
+    pub fn are_neighbors(&self, a: i32, b: i32) -> bool {
+        self.links.get(&a).is_some_and(|links| links.contains(&b))
+    }
+
+    /// Returns the number of links on the shortest path from `from` to every
+    /// node it can reach
+    pub fn distances(&self, from: i32) -> BTreeMap<i32, usize> {
+        let mut distances = BTreeMap::from([(from, 0)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(node) = queue.pop_front() {
+            let distance = distances[&node];
+            for neighbor in self.neighbors(node) {
+                distances.entry(neighbor).or_insert_with(|| {
+                    queue.push_back(neighbor);
+                    distance + 1
+                });
+            }
+        }
+        distances
+    }
+
+    /// Returns true if every node can reach every other node
+    pub fn is_connected(&self) -> bool {
+        match self.links.keys().next() {
+            Some(&first) => self.distances(first).len() == self.len(),
+            None => true,
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# {} nodes", self.len())?;
+        for (a, b) in self.edges() {
+            writeln!(f, "{} {}", a, b)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses an edge list: one link per line as two node idents separated by
+/// whitespace. Blank lines and lines starting with `#` are ignored, except
+/// for a `# <n> nodes` line, which adds nodes without links.
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut edges = Vec::new();
+        let mut nodes: usize = 0;
+        for line in s.lines().map(str::trim) {
+            if let Some(comment) = line.strip_prefix('#') {
+                if let [count, "nodes"] = comment.split_whitespace().collect::<Vec<_>>()[..] {
+                    if let Ok(count) = count.parse() {
+                        nodes = count;
+                    }
+                }
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let idents = line
+                .split_whitespace()
+                .map(|word| {
+                    word.parse::<i32>()
+                        .map_err(|_| format!("invalid node ident: {:?}", word))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            match idents[..] {
+                [a, b] if a >= 0 && b >= 0 && a != b => edges.push((a, b)),
+                _ => return Err(format!("invalid link: {:?}", line)),
+            }
+        }
+        let mut topology = Self::from_edges(edges);
+        let seen: HashSet<i32> = topology.links.keys().copied().collect();
+        for node in (0..nodes as i32).filter(|node| !seen.contains(node)) {
+            topology.links.insert(node, BTreeSet::new());
+        }
+        Ok(topology)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn degrees(topology: &Topology) -> BTreeSet<usize> {
+        (0..topology.len() as i32)
+            .map(|node| topology.neighbors(node).len())
+            .collect()
+    }
+
+    #[test]
+    fn test_generators() {
+        let ring = Topology::ring(5);
+        assert_eq!(ring.neighbors(0), vec![1, 4]);
+        assert_eq!(ring.distances(0)[&3], 2);
+        assert_eq!(Topology::complete(4).edges().len(), 6);
+
+        let mut rng = SimRng::new(0);
+        let regular = Topology::random_regular(10, 3, &mut rng);
+        assert_eq!(degrees(&regular), BTreeSet::from([3]));
+        assert_eq!(regular.edges().len(), 15);
+
+        let world = Topology::small_world(20, 4, 0.2, &mut rng);
+        assert_eq!(world.edges().len(), 40);
+        assert_ne!(world, Topology::small_world(20, 4, 0.0, &mut rng));
+
+        assert!(Topology::erdos_renyi(8, 0.0, &mut rng).edges().is_empty());
+        assert_eq!(
+            Topology::erdos_renyi(8, 1.0, &mut rng),
+            Topology::complete(8)
+        );
+        assert!(!Topology::new(2).is_connected());
+    }
+
+    #[test]
+    fn test_edge_list() {
+        let topology: Topology = "# 4 nodes\n0 1\n\n1 2 \n# a comment\n".parse().unwrap();
+        assert_eq!(topology.len(), 4);
+        assert_eq!(topology.edges(), vec![(0, 1), (1, 2)]);
+        assert!(!topology.is_connected());
+        assert_eq!(topology.to_string().parse::<Topology>().unwrap(), topology);
+
+        // Only `# <n> nodes` counts nodes; other comments are ignored
+        let topology: Topology = "# links between nodes\n# 3 more nodes\n# 3 nodes\n0 1\n"
+            .parse()
+            .unwrap();
+        assert_eq!(topology.len(), 3);
+
+        assert!("0 1 2".parse::<Topology>().is_err());
+        assert!("0 0".parse::<Topology>().is_err());
+        assert!("0 x".parse::<Topology>().is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::Time;
+
+/// What a trace entry records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TraceKind {
+    /// A message was handed to the network
+    Send,
+    /// A message reached its target
+    Deliver,
+    /// A message was held back by a partition until it heals
+    Hold,
+    /// A message was lost: to a fault when sent, or to a partition or a
+    /// crashed target when delivered
+    Drop,
+    /// A timer fired
+    Timer,
+    /// A node was started
+    Start,
+    /// A node handled a message it had spent processing time on
+    Handle,
+    /// The partitioning of the network changed
+    Partition,
+    /// A node crashed
+    Crash,
+    /// A crashed node restarted
+    Recover,
+}
+
+impl TraceKind {
+    fn name(&self) -> &'static str {
+        match self {
+            TraceKind::Send => "send",
+            TraceKind::Deliver => "deliver",
+            TraceKind::Hold => "hold",
+            TraceKind::Drop => "drop",
+            TraceKind::Timer => "timer",
+            TraceKind::Start => "start",
+            TraceKind::Handle => "handle",
+            TraceKind::Partition => "partition",
+            TraceKind::Crash => "crash",
+            TraceKind::Recover => "recover",
+        }
+    }
+}
+
+impl FromStr for TraceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            TraceKind::Send,
+            TraceKind::Deliver,
+            TraceKind::Hold,
+            TraceKind::Drop,
+            TraceKind::Timer,
+            TraceKind::Start,
+            TraceKind::Handle,
+            TraceKind::Partition,
+            TraceKind::Crash,
+            TraceKind::Recover,
+        ]
+        .into_iter()
+        .find(|kind| kind.name() == s)
+        .ok_or_else(|| format!("unknown trace entry kind: {:?}", s))
+    }
+}
+
+/// One recorded step of a run
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TraceEntry {
+    /// Virtual time of the entry
+    pub time: Time,
+    pub kind: TraceKind,
+    /// Sequence number of the scheduler event the entry refers to. Entries of
+    /// processed events have one; a message dropped before it was scheduled
+    /// does not.
+    pub seq: Option<u64>,
+    pub sender: i32,
+    pub target: i32,
+    /// Debug form of the message, or a description of the event
+    pub message: String,
+}
+
+impl TraceEntry {
+    /// Returns true if the entry records an event taken off the scheduler queue
+    pub fn is_processed_event(&self) -> bool {
+        self.kind != TraceKind::Send && self.seq.is_some()
+    }
+}
+
+/// A recording of a run: every send, delivery, drop, timer firing and other
+/// processed event, in order.
+///
+/// Traces are stored as text, one tab-separated entry per line, so they can
+/// be saved from CI and replayed locally with `Network::replay`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+}
+
+const HEADER: &str = "# time\tkind\tseq\tsender\ttarget\tmessage";
+
+impl Trace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, entry: TraceEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    /// Returns the sequence numbers of the processed events, in processing order
+    pub fn processing_order(&self) -> Vec<u64> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.is_processed_event())
+            .filter_map(|entry| entry.seq)
+            .collect()
+    }
+
+    /// Writes the trace to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Reads a trace written by `save`
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        std::fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for entry in &self.entries {
+            let seq = entry.seq.map_or("-".to_string(), |seq| seq.to_string());
+            writeln!(
+                f,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                entry.time,
+                entry.kind.name(),
+                seq,
+                entry.sender,
+                entry.target,
+                entry.message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_entry(line: &str) -> Result<TraceEntry, String> {
+    let fields: Vec<&str> = line.splitn(6, '\t').collect();
+    let [time, kind, seq, sender, target, message] = fields[..] else {
+        return Err(format!("expected 6 tab-separated fields: {:?}", line));
+    };
+    let number = |field: &str| format!("invalid number {:?} in {:?}", field, line);
+    Ok(TraceEntry {
+        time: time.parse().map_err(|_| number(time))?,
+        kind: kind.parse()?,
+        seq: match seq {
+            "-" => None,
+            seq => Some(seq.parse().map_err(|_| number(seq))?),
+        },
+        sender: sender.parse().map_err(|_| number(sender))?,
+        target: target.parse().map_err(|_| number(target))?,
+        message: message.to_string(),
+    })
+}
+
+impl FromStr for Trace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let entries = s
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(parse_entry)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Trace { entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut trace = Trace::new();
+        trace.push(TraceEntry {
+            time: 0,
+            kind: TraceKind::Send,
+            seq: Some(3),
+            sender: 1,
+            target: 2,
+            message: "Ping(\"a\\tb\")".to_string(),
+        });
+        trace.push(TraceEntry {
+            time: 4,
+            kind: TraceKind::Drop,
+            seq: None,
+            sender: 1,
+            target: 2,
+            message: "Ping(1)".to_string(),
+        });
+        trace.push(TraceEntry {
+            time: 4,
+            kind: TraceKind::Deliver,
+            seq: Some(3),
+            sender: 1,
+            target: 2,
+            message: "Ping(\"a\\tb\")".to_string(),
+        });
+
+        let parsed: Trace = trace.to_string().parse().unwrap();
+        assert_eq!(parsed, trace);
+        assert_eq!(parsed.processing_order(), vec![3]);
+        assert!("0\tsend\t1".parse::<Trace>().is_err());
+    }
+}
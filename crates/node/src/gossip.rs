@@ -0,0 +1,426 @@
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use message::Envelope;
+use network::{NetworkHandle, Node, Time};
+use utils::{skip, ProcessEffect, SimRng};
+
+/// Timer tag a gossip node uses for its pull rounds, just below the tags of
+/// RPC clients. Nodes embedding a `GossipNode` pass timers with this tag on
+/// to it.
+pub const PULL_TAG: u64 = network::rpc::RPC_TAG - 1;
+
+/// Identifies a gossiped message: the node that published it and how many
+/// messages that node published before
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GossipId {
+    pub origin: i32,
+    pub seq: u64,
+}
+
+/// A gossiped payload, pushed to peers while its TTL lasts
+#[derive(Debug, Clone)]
+pub struct Gossip<P> {
+    pub id: GossipId,
+    /// Number of further hops the message is pushed
+    pub ttl: u32,
+    pub payload: P,
+}
+
+/// Asks a peer for the cached messages not in `have`
+#[derive(Debug, Clone)]
+pub struct PullRequest {
+    pub have: Vec<GossipId>,
+}
+
+/// How a gossip node disseminates messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GossipConfig {
+    /// Number of random neighbors a new message is pushed to
+    pub fanout: usize,
+    /// Number of hops a published message is pushed
+    pub ttl: u32,
+    /// Number of messages kept for deduplication and pull requests; older
+    /// ones are forgotten, with the time they were first seen, and would be
+    /// accepted again
+    pub cache_size: usize,
+    /// Time between pull rounds, or None to only push
+    pub pull_interval: Option<Time>,
+    /// A node stops pulling after this many rounds in a row that brought
+    /// nothing new, so that the network can go quiet
+    pub pull_rounds: u32,
+}
+
+impl GossipConfig {
+    /// Pushes to `fanout` neighbors with an unlimited TTL and no pulling
+    pub fn new(fanout: usize) -> Self {
+        GossipConfig {
+            fanout,
+            ttl: u32::MAX,
+            cache_size: 1024,
+            pull_interval: None,
+            pull_rounds: 0,
+        }
+    }
+
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn cache_size(mut self, cache_size: usize) -> Self {
+        self.cache_size = cache_size;
+        self
+    }
+
+    /// Also pulls from a random neighbor every `interval`, until `rounds`
+    /// rounds in a row bring nothing new
+    pub fn pull(mut self, interval: Time, rounds: u32) -> Self {
+        self.pull_interval = Some(interval);
+        self.pull_rounds = rounds;
+        self
+    }
+}
+
+/// Counters of a gossip node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GossipStats {
+    /// Messages received for the first time, or published by the node
+    pub delivered: u64,
+    /// Messages received again while still cached
+    pub duplicates: u64,
+    /// Gossip messages sent, pushed or in reply to pulls
+    pub pushes_sent: u64,
+    pub pulls_sent: u64,
+}
+
+impl GossipStats {
+    /// Returns the number of duplicate receptions per delivered message
+    pub fn redundancy(&self) -> f64 {
+        if self.delivered == 0 {
+            0.0
+        } else {
+            self.duplicates as f64 / self.delivered as f64
+        }
+    }
+}
+
+impl std::ops::Add for GossipStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        GossipStats {
+            delivered: self.delivered + other.delivered,
+            duplicates: self.duplicates + other.duplicates,
+            pushes_sent: self.pushes_sent + other.pushes_sent,
+            pulls_sent: self.pulls_sent + other.pulls_sent,
+        }
+    }
+}
+
+struct GossipState<P> {
+    published: u64,
+    /// Cached ids, oldest first
+    order: VecDeque<GossipId>,
+    cache: HashMap<GossipId, Gossip<P>>,
+    first_seen: HashMap<GossipId, Time>,
+    stats: GossipStats,
+    idle_rounds: u32,
+    pulling: bool,
+}
+
+/// What the node does after updating its state
+enum Action<P> {
+    Push(Gossip<P>, Option<i32>),
+    Reply(i32, Vec<Gossip<P>>),
+    Pull(Vec<GossipId>),
+    Nothing,
+}
+
+/// A node that disseminates messages by push/pull gossip instead of
+/// `Network::broadcast`.
+///
+/// A new message is pushed to `fanout` random neighbors, which push it on
+/// until its TTL runs out. With pulling enabled, nodes also periodically ask
+/// a random neighbor for the messages they are missing. Protocols embed a
+/// `GossipNode` and pass their envelopes to `receive_gossip`, which hands
+/// back the payloads delivered for the first time.
+pub struct GossipNode<P> {
+    ident: i32,
+    network: Option<NetworkHandle>,
+    config: GossipConfig,
+    state: std::sync::Mutex<GossipState<P>>,
+    /// Picks push targets and pull peers, seeded per node from the simulation
+    rng: std::sync::Mutex<SimRng>,
+}
+
+impl<P> GossipNode<P>
+where
+    P: std::fmt::Debug + Clone + Send + Sync + 'static,
+{
+    pub fn new(config: GossipConfig) -> Self {
+        GossipNode {
+            ident: 0,
+            network: None,
+            config,
+            rng: std::sync::Mutex::new(SimRng::new(0)),
+            state: std::sync::Mutex::new(GossipState {
+                published: 0,
+                order: VecDeque::new(),
+                cache: HashMap::new(),
+                first_seen: HashMap::new(),
+                stats: GossipStats::default(),
+                idle_rounds: 0,
+                pulling: false,
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &GossipConfig {
+        &self.config
+    }
+
+    pub fn stats(&self) -> GossipStats {
+        self.state().stats
+    }
+
+    /// Returns the virtual time the node first saw message `id`, if it did
+    /// and the message is still cached
+    pub fn first_seen(&self, id: GossipId) -> Option<Time> {
+        self.state().first_seen.get(&id).copied()
+    }
+
+    /// Returns how long message `id` took from its publication until every
+    /// node in `nodes` had it. `nodes` must include the publisher, whose
+    /// first sighting is the publication. Returns None if the publisher is
+    /// missing or some node never got the message.
+    pub fn coverage_time(nodes: &[&GossipNode<P>], id: GossipId) -> Option<Time> {
+        let publisher = nodes.iter().find(|node| node.ident == id.origin)?;
+        let published = publisher.first_seen(id)?;
+        let times = nodes
+            .iter()
+            .map(|node| node.first_seen(id))
+            .collect::<Option<Vec<Time>>>()?;
+        Some(times.into_iter().max()? - published)
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, GossipState<P>> {
+        self.state.lock().expect("gossip state poisoned")
+    }
+
+    /// Caches a message unless it is cached already; returns true if new
+    fn remember(&self, gossip: &Gossip<P>, now: Time) -> bool {
+        let mut state = self.state();
+        if state.cache.contains_key(&gossip.id) {
+            state.stats.duplicates += 1;
+            return false;
+        }
+        if state.order.len() >= self.config.cache_size {
+            if let Some(oldest) = state.order.pop_front() {
+                state.cache.remove(&oldest);
+                state.first_seen.remove(&oldest);
+            }
+        }
+        state.order.push_back(gossip.id);
+        state.cache.insert(gossip.id, gossip.clone());
+        state.first_seen.entry(gossip.id).or_insert(now);
+        state.stats.delivered += 1;
+        state.idle_rounds = 0;
+        true
+    }
+
+    /// Publishes a new message and starts pushing it; returns its id
+    pub async fn publish(&self, payload: P) -> GossipId {
+        let id = {
+            let mut state = self.state();
+            state.published += 1;
+            GossipId {
+                origin: self.ident,
+                seq: state.published - 1,
+            }
+        };
+        let gossip = Gossip {
+            id,
+            ttl: self.config.ttl.saturating_sub(1),
+            payload,
+        };
+        let now = self.network().now();
+        self.remember(&gossip, now);
+        self.log("publish", &format!("{:?}", gossip)).await;
+        if self.config.ttl > 0 {
+            self.act(Action::Push(gossip, None));
+        }
+        self.start_pulling().await;
+        id
+    }
+
+    /// Handles a message received by the node. Returns the payload of a
+    /// message seen for the first time, None for other gossip traffic, and
+    /// the envelope back if it is not gossip at all.
+    pub async fn receive_gossip(&self, envelope: Envelope) -> Result<Option<P>, Envelope> {
+        let gossip = envelope.downcast_ref::<Gossip<P>>().cloned();
+        if let Some(gossip) = gossip {
+            if !self.remember(&gossip, envelope.delivered_at) {
+                return Ok(None);
+            }
+            let payload = gossip.payload.clone();
+            if gossip.ttl > 0 {
+                let forward = Gossip {
+                    ttl: gossip.ttl - 1,
+                    ..gossip
+                };
+                self.act(Action::Push(forward, Some(envelope.sender)));
+            }
+            self.start_pulling().await;
+            return Ok(Some(payload));
+        }
+        let request = envelope.downcast_ref::<PullRequest>().cloned();
+        if let Some(request) = request {
+            let missing: Vec<Gossip<P>> = {
+                let have: HashSet<GossipId> = request.have.into_iter().collect();
+                let state = self.state();
+                state
+                    .order
+                    .iter()
+                    .filter(|id| !have.contains(id))
+                    .map(|id| state.cache[id].clone())
+                    .collect()
+            };
+            self.act(Action::Reply(envelope.sender, missing));
+            return Ok(None);
+        }
+        Err(envelope)
+    }
+
+    /// Runs a pull round if one is due. Returns false for timers that are
+    /// not the node's pull timer.
+    pub async fn on_pull_timer(&self, tag: u64) -> bool {
+        if tag != PULL_TAG {
+            return false;
+        }
+        let action = {
+            let mut state = self.state();
+            if state.idle_rounds >= self.config.pull_rounds {
+                state.pulling = false;
+                Action::Nothing
+            } else {
+                state.idle_rounds += 1;
+                Action::Pull(state.order.iter().copied().collect())
+            }
+        };
+        self.act(action);
+        true
+    }
+
+    /// Starts the pull rounds, if enabled
+    pub async fn start_pulling(&self) {
+        let Some(interval) = self.config.pull_interval else {
+            return;
+        };
+        {
+            let mut state = self.state();
+            if state.pulling {
+                return;
+            }
+            state.pulling = true;
+        }
+        self.network().set_timer(self.ident, interval, PULL_TAG);
+    }
+
+    fn act(&self, action: Action<P>) {
+        let network = self.network();
+        let mut rng = self.rng.lock().expect("gossip rng poisoned");
+        let sent = match action {
+            Action::Push(gossip, from) => {
+                let mut targets: Vec<i32> = network
+                    .neighbors(self.ident)
+                    .into_iter()
+                    .filter(|target| Some(*target) != from)
+                    .collect();
+                rng.shuffle(&mut targets);
+                targets.truncate(self.config.fanout);
+                for target in &targets {
+                    network.send(self.ident, *target, Box::new(gossip.clone()), None);
+                }
+                targets.len()
+            }
+            Action::Reply(target, missing) => {
+                let count = missing.len();
+                for gossip in missing {
+                    network.send(self.ident, target, Box::new(gossip), None);
+                }
+                count
+            }
+            Action::Pull(have) => {
+                let neighbors = network.neighbors(self.ident);
+                if let Some(&target) = rng.choose(&neighbors) {
+                    let request = PullRequest { have };
+                    network.send(self.ident, target, Box::new(request), None);
+                    self.state().stats.pulls_sent += 1;
+                }
+                if let Some(interval) = self.config.pull_interval {
+                    network.set_timer(self.ident, interval, PULL_TAG);
+                }
+                0
+            }
+            Action::Nothing => 0,
+        };
+        self.state().stats.pushes_sent += sent as u64;
+    }
+}
+
+impl<P> std::fmt::Debug for GossipNode<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GossipNode({})", self.ident)
+    }
+}
+
+#[async_trait]
+impl<P> Node for GossipNode<P>
+where
+    P: std::fmt::Debug + Clone + Send + Sync + 'static,
+{
+    fn initialize(&mut self, ident: i32, network: NetworkHandle) {
+        self.ident = ident;
+        self.rng = std::sync::Mutex::new(network.rng(ident));
+        self.network = Some(network);
+    }
+
+    fn ident(&self) -> i32 {
+        self.ident
+    }
+
+    fn network(&self) -> &NetworkHandle {
+        self.network.as_ref().expect("Node not initialized")
+    }
+
+    async fn on_timer(&self, tag: u64) -> ProcessEffect {
+        self.on_pull_timer(tag).await;
+        skip().await
+    }
+
+    /// The crash discards the pending pull timer, so pulling starts over
+    /// when the node recovers; the cache is kept as persisted state
+    fn on_crash(&self) {
+        let mut state = self.state();
+        state.pulling = false;
+        state.idle_rounds = 0;
+    }
+
+    async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+        match self.receive_gossip(envelope).await {
+            Ok(Some(payload)) => self.log("deliver", &format!("{:?}", payload)).await,
+            Ok(None) => {}
+            Err(envelope) => {
+                let detail = format!("from {}: {:?}", envelope.sender, envelope.message);
+                self.log("unhandled", &detail).await;
+            }
+        }
+        skip().await
+    }
+
+    async fn run(&self) -> ProcessEffect {
+        self.start_pulling().await;
+        skip().await
+    }
+}
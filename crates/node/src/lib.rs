@@ -1,18 +1,20 @@
 use async_trait::async_trait;
-use std::collections::VecDeque;
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
 use logging::{Logger, DebugLogger};
 #[allow(unused_imports)]
-use message::{Message, MessageString};
-use network::{Network, Node};
+use message::{Envelope, Message, MessageString};
+use network::{Adversary, NetworkHandle, Node, Time};
 use utils::{skip, ProcessEffect};
 
+pub mod gossip;
+
+pub use gossip::{Gossip, GossipConfig, GossipId, GossipNode, GossipStats, PullRequest};
+
 #[allow(dead_code)]
 pub struct PassiveNode {
     id: i32,
-    network: Option<Arc<Mutex<Network>>>,
+    network: Option<NetworkHandle>,
     logger: Arc<dyn Logger>,
 }
 
@@ -26,6 +28,12 @@ impl PassiveNode {
     }
 }
 
+impl Default for PassiveNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl std::fmt::Debug for PassiveNode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "PassiveNode({})", self.id)
@@ -34,7 +42,7 @@ impl std::fmt::Debug for PassiveNode {
 
 #[async_trait]
 impl Node for PassiveNode {
-    fn initialize(&mut self, ident: i32, network: Arc<Mutex<Network>>) {
+    fn initialize(&mut self, ident: i32, network: NetworkHandle) {
         self.id = ident;
         self.network = Some(network);
     }
@@ -43,12 +51,12 @@ impl Node for PassiveNode {
         self.id
     }
 
-    fn network(&self) -> Arc<Mutex<Network>> {
-        self.network.clone().expect("Node not initialized")
+    fn network(&self) -> &NetworkHandle {
+        self.network.as_ref().expect("Node not initialized")
     }
 
-    async fn handle(&self, sender: i32, message: Box<dyn Message>) -> ProcessEffect {
-        self.log("RECEIVE", &format!("from {}: {:?}", sender, message)).await;
+    async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+        self.log("RECEIVE", &format!("from {}: {:?}", envelope.sender, envelope.message)).await;
         skip().await
     }
 
@@ -56,13 +64,19 @@ impl Node for PassiveNode {
         self.log("RUN", "passive node").await;
         skip().await
     }
+
+    fn state_hash(&self) -> Option<u64> {
+        Some(0)
+    }
 }
 
-/// A node that processes messages sequentially
+/// A node that processes messages sequentially.
+///
+/// Deliveries wait in a mailbox kept by the network and are handed to
+/// `handle` one at a time, the next one only after the previous one finished.
 pub struct SequentialNode {
     ident: i32,
-    network: Option<Arc<Mutex<Network>>>,
-    pub mailbox: Arc<Mutex<VecDeque<(i32, Box<dyn Message>)>>>,
+    network: Option<NetworkHandle>,
 }
 
 impl SequentialNode {
@@ -70,7 +84,6 @@ impl SequentialNode {
         SequentialNode {
             ident: 0,
             network: None,
-            mailbox: Arc::new(Mutex::new(VecDeque::new())),
         }
     }
 }
@@ -89,7 +102,7 @@ impl std::fmt::Debug for SequentialNode {
 
 #[async_trait]
 impl Node for SequentialNode {
-    fn initialize(&mut self, ident: i32, network: Arc<Mutex<Network>>) {
+    fn initialize(&mut self, ident: i32, network: NetworkHandle) {
         self.ident = ident;
         self.network = Some(network);
     }
@@ -98,33 +111,108 @@ impl Node for SequentialNode {
         self.ident
     }
 
-    fn network(&self) -> Arc<Mutex<Network>> {
-        self.network.as_ref().expect("Node not initialized").clone()
+    fn network(&self) -> &NetworkHandle {
+        self.network.as_ref().expect("Node not initialized")
     }
 
-    async fn handle(&self, _sender: i32, _message: Box<dyn Message>) -> ProcessEffect {
-        skip().await
+    fn is_sequential(&self) -> bool {
+        true
     }
 
-    async fn receive(&self, sender: i32, message: Box<dyn Message>) -> ProcessEffect {
-        let mut mailbox = self.mailbox.lock().await;
-        mailbox.push_back((sender, message));
+    fn state_hash(&self) -> Option<u64> {
+        Some(0)
+    }
+
+    async fn handle(&self, _envelope: Envelope) -> ProcessEffect {
         skip().await
     }
 
     async fn run(&self) -> ProcessEffect {
-        loop {
-            let maybe_message = {
-                let mut mailbox = self.mailbox.lock().await;
-                mailbox.pop_front()
-            };
-
-            if let Some((sender, message)) = maybe_message {
-                self.log("handle", &format!("from {:2}: {:?}", sender, message)).await;
-                self.handle(sender, message).await;
-            } else {
-                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-            }
+        skip().await
+    }
+}
+
+/// A Byzantine node: runs the code of an honest node, while an adversary
+/// decides what it actually sends.
+///
+/// Wrapping lets the same protocol implementation be used for honest and
+/// faulty nodes. Everything except sending is left to the honest node.
+pub struct ByzantineNode<N> {
+    honest: N,
+    adversary: std::sync::Mutex<Box<dyn Adversary>>,
+}
+
+impl<N: Node> ByzantineNode<N> {
+    pub fn new(honest: N, adversary: impl Adversary + 'static) -> Self {
+        ByzantineNode {
+            honest,
+            adversary: std::sync::Mutex::new(Box::new(adversary)),
         }
     }
+
+    /// Returns the wrapped honest node
+    pub fn honest(&self) -> &N {
+        &self.honest
+    }
+}
+
+impl<N: Node> std::fmt::Debug for ByzantineNode<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ByzantineNode({:?})", self.honest)
+    }
+}
+
+#[async_trait]
+impl<N: Node> Node for ByzantineNode<N> {
+    fn initialize(&mut self, ident: i32, network: NetworkHandle) {
+        self.honest.initialize(ident, network);
+    }
+
+    fn ident(&self) -> i32 {
+        self.honest.ident()
+    }
+
+    fn network(&self) -> &NetworkHandle {
+        self.honest.network()
+    }
+
+    fn is_sequential(&self) -> bool {
+        self.honest.is_sequential()
+    }
+
+    fn processing_time(&self, message: &dyn Message) -> Time {
+        self.honest.processing_time(message)
+    }
+
+    fn on_crash(&self) {
+        self.honest.on_crash()
+    }
+
+    fn adversary(&self) -> Option<&std::sync::Mutex<Box<dyn Adversary>>> {
+        Some(&self.adversary)
+    }
+
+    fn state_hash(&self) -> Option<u64> {
+        self.honest.state_hash()
+    }
+
+    async fn on_timer(&self, tag: u64) -> ProcessEffect {
+        self.honest.on_timer(tag).await
+    }
+
+    async fn receive(&self, envelope: Envelope) -> ProcessEffect {
+        self.honest.receive(envelope).await
+    }
+
+    async fn handle(&self, envelope: Envelope) -> ProcessEffect {
+        self.honest.handle(envelope).await
+    }
+
+    async fn recover(&self) -> ProcessEffect {
+        self.honest.recover().await
+    }
+
+    async fn run(&self) -> ProcessEffect {
+        self.honest.run().await
+    }
 }
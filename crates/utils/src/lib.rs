@@ -1,6 +1,10 @@
 use std::hash::{Hash, Hasher};
 use tokio::sync::oneshot; // For Event-like functionality
 
+pub mod rng;
+
+pub use rng::SimRng;
+
 /// Type alias for process effects, similar to Python's Generator[Event, None, None]
 pub type ProcessEffect = oneshot::Receiver<()>;
 
@@ -26,6 +30,12 @@ impl Unique {
     }
 }
 
+impl Default for Unique {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // Implement equality based on memory address
 impl PartialEq for Unique {
     fn eq(&self, other: &Self) -> bool {
@@ -42,6 +52,28 @@ impl Hash for Unique {
     }
 }
 
+/// Allocates sequential identifiers.
+///
+/// Each simulation or context owns its own allocator and threads it through
+/// constructors, so ID sequences don't depend on what else ran in the process.
+#[derive(Debug, Default, Clone)]
+pub struct IdAllocator {
+    next: u64,
+}
+
+impl IdAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the next unused identifier
+    pub fn next_id(&mut self) -> u64 {
+        let id = self.next;
+        self.next += 1;
+        id
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -59,4 +91,13 @@ mod tests {
         set.insert(u2);
         assert_eq!(set.len(), 2);
     }
+
+    #[test]
+    fn test_id_allocators_are_independent() {
+        let mut a = IdAllocator::new();
+        let mut b = IdAllocator::new();
+        assert_eq!(a.next_id(), 0);
+        assert_eq!(a.next_id(), 1);
+        assert_eq!(b.next_id(), 0);
+    }
 }
@@ -0,0 +1,146 @@
+use std::ops::{Range, RangeInclusive};
+
+/// Separates derived streams from generators seeded directly
+const STREAM_DOMAIN: u64 = 0x5851_f42d_4c95_7f2d;
+
+/// The SplitMix64 finalizer: a bijective mix of all 64 bits
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Deterministic pseudo-random number generator (xoshiro256**).
+///
+/// The generator is implemented here rather than pulled from a crate so that
+/// a given seed produces the same stream on every platform and toolchain.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimRng {
+    state: [u64; 4],
+}
+
+impl SimRng {
+    /// Creates a generator from a seed, expanding it with SplitMix64
+    pub fn new(seed: u64) -> Self {
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            mix(x)
+        };
+        SimRng {
+            state: [next(), next(), next(), next()],
+        }
+    }
+
+    /// Creates the generator of an independent stream derived from a seed,
+    /// for components that need randomness of their own. Seed and stream are
+    /// hashed together under a domain constant, so no stream shares its
+    /// state sequence with `SimRng::new(seed)` or with another stream.
+    pub fn stream(seed: u64, stream: u64) -> Self {
+        SimRng::new(mix(mix(seed ^ STREAM_DOMAIN) ^ stream.wrapping_add(1)))
+    }
+
+    /// Returns the next 64 random bits
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        result
+    }
+
+    /// Returns a float uniformly distributed in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Returns an integer uniformly distributed in `range`; the range must not be empty
+    pub fn gen_range(&mut self, range: Range<u64>) -> u64 {
+        assert!(range.start < range.end, "empty range");
+        let span = range.end - range.start;
+        // Reject the top partial block so that every value is equally likely
+        let zone = u64::MAX - (u64::MAX - span + 1) % span;
+        loop {
+            let value = self.next_u64();
+            if value <= zone {
+                return range.start + value % span;
+            }
+        }
+    }
+
+    /// Returns an integer uniformly distributed in `range`, which may reach
+    /// `u64::MAX`; the range must not be empty
+    pub fn gen_range_inclusive(&mut self, range: RangeInclusive<u64>) -> u64 {
+        let (start, end) = range.into_inner();
+        assert!(start <= end, "empty range");
+        match end.checked_add(1) {
+            Some(end) => self.gen_range(start..end),
+            None if start == 0 => self.next_u64(),
+            None => start + self.gen_range(0..u64::MAX - start + 1),
+        }
+    }
+
+    /// Returns true with probability `p`
+    pub fn gen_bool(&mut self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+
+    /// Returns a uniformly chosen element of `items`, or None if it is empty
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            items.get(self.gen_range(0..items.len() as u64) as usize)
+        }
+    }
+
+    /// Shuffles `items` in place (Fisher-Yates)
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.gen_range(0..i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_stream() {
+        let mut a = SimRng::new(42);
+        let mut b = SimRng::new(42);
+        let mut c = SimRng::new(43);
+
+        let xs: Vec<u64> = (0..8).map(|_| a.next_u64()).collect();
+        let ys: Vec<u64> = (0..8).map(|_| b.next_u64()).collect();
+        let zs: Vec<u64> = (0..8).map(|_| c.next_u64()).collect();
+        assert_eq!(xs, ys);
+        assert_ne!(xs, zs);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut rng = SimRng::new(7);
+        for _ in 0..1000 {
+            assert!((3..9).contains(&rng.gen_range(3..9)));
+            assert!((0.0..1.0).contains(&rng.next_f64()));
+        }
+        assert_eq!(rng.choose::<i32>(&[]), None);
+        assert_eq!(rng.gen_range_inclusive(4..=4), 4);
+        assert!(rng.gen_range_inclusive(u64::MAX - 1..=u64::MAX) >= u64::MAX - 1);
+        rng.gen_range_inclusive(0..=u64::MAX);
+
+        let mut items: Vec<i32> = (0..10).collect();
+        rng.shuffle(&mut items);
+        items.sort();
+        assert_eq!(items, (0..10).collect::<Vec<_>>());
+    }
+}